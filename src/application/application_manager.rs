@@ -6,28 +6,96 @@
 //! - Setup input callbacks from the kernel which then are passed to the application
 //! - Start executing
 //! 
-//! Due to the abstract nature of the `ApplicationManager` it is possible to run more than one simultaneously
-//! provided you have the available RAM
+//! Due to the abstract nature of the `ApplicationManager` it is possible to hold more than one application
+//! provided you have the available RAM, each in its own slot. Applications are loaded into the load slot
+//! (see `select_slot`) and the active slot (see `switch_slot`) is executed.
 
 use crc::crc32::checksum_ieee;
 use crate::types::{Context, ServiceFn, SetupFn, Ssd1351, InputFn, InputEvent};
+use heapless::consts::*;
+use heapless::{String, Vec};
+
+/// The length of an application checksum (CRC32) in bytes
+pub const CHECKSUM_LEN: usize = 4;
+/// The maximum number of application slots
+pub const MAX_SLOTS: usize = 4;
 
 /// Application manager
 pub struct ApplicationManager {
+    slots: Vec<Slot, U4>,
+    /// the slot applications are loaded into
+    load: usize,
+    /// the slot that is executed
+    active: usize,
+    progress_fn: Option<fn(usize)>,
+    now_ms: u32,
+    exec_timeout_ms: Option<u32>,
+    last_pet_ms: u32,
+    /// set by the application through its `Context`
+    petted: bool,
+}
+
+/// A region of ram an application can be loaded into, applications must be linked for the address of the slot
+struct Slot {
     ram: Ram,
-    target_cs: [u8; 4],
+    target_cs: [u8; CHECKSUM_LEN],
     target_cs_idx: usize,
     service_fn: Option<ServiceFn>,
     input_fn: Option<InputFn>,
     status: Status,
+    name: String<U16>,
+    version: String<U16>,
+    /// a byte of the image did not fit in ram
+    overflowed: bool,
+    /// the length of the image received without a gap from its start
+    received: usize,
+}
+
+impl Slot {
+    fn new(ram: Ram) -> Self {
+        Self {
+            ram: ram,
+            target_cs: [0u8; CHECKSUM_LEN],
+            target_cs_idx: 0,
+            service_fn: None,
+            input_fn: None,
+            status: Status::default(),
+            name: String::new(),
+            version: String::new(),
+            overflowed: false,
+            received: 0,
+        }
+    }
+
+    /// Stop the application and unload it from memory
+    fn kill(&mut self) {
+        self.ram.reset();
+        self.target_cs_idx = 0;
+        self.status.is_loaded = false;
+        self.status.is_running = false;
+        self.status.ram_used = 0;
+        self.input_fn = None;
+        self.service_fn = None;
+        self.name = String::new();
+        self.version = String::new();
+        self.overflowed = false;
+        self.received = 0;
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     /// The applicaton is running
     Executing,
-    /// Failed checksum of ram
-    ChecksumFailed,
+    /// The checksum of ram did not match the checksum sent with the application
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// The application has no image
+    EmptyImage,
+    /// The image did not fit in ram
+    TooLarge,
+    /// The checksum was not fully received
+    NotStaged,
     /// No application has been loaded
     NoApplication,
     /// The FFI function pointer for service is invalid
@@ -35,7 +103,13 @@ pub enum Error {
     /// The FFI function pointer for input is invalid
     InvalidInputFn,
     /// The application doesnt fit in memory
-    NoMemory
+    NoMemory,
+    /// The application did not pet the watchdog in time and was killed
+    WatchdogTimeout,
+    /// There is no slot with this index
+    InvalidSlot(usize),
+    /// The application name or version is too long, or not ASCII
+    InvalidMetadata,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -59,46 +133,239 @@ impl Default for Status {
 
 impl ApplicationManager {
     
-    /// Create a new application manager from a chunk of ram
+    /// Create a new application manager from a chunk of ram, used as the first slot
     pub fn new(ram: Ram) -> Self {
+        let mut slots = Vec::new();
+        slots.push(Slot::new(ram)).ok();
         Self {
-            ram: ram,
-            target_cs: [0u8; 4],
-            target_cs_idx: 0,
-            service_fn: None,
-            input_fn: None,
-            status: Status::default(),
+            slots,
+            load: 0,
+            active: 0,
+            progress_fn: None,
+            now_ms: 0,
+            exec_timeout_ms: None,
+            last_pet_ms: 0,
+            petted: false,
         }
     }
 
-    /// Write a byte into the managers internal ram
+    /// Add another slot applications can be loaded into, returning its index
+    pub fn add_slot(&mut self, ram: Ram) -> Result<usize, Error> {
+        self.slots.push(Slot::new(ram)).map_err(|_| Error::InvalidSlot(MAX_SLOTS))?;
+        Ok(self.slots.len() - 1)
+    }
+
+    /// The number of slots
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Select the slot the next application is loaded into, which cannot be the slot that is executing
+    pub fn select_slot(&mut self, slot: usize) -> Result<(), Error> {
+        if slot >= self.slots.len() {
+            return Err(Error::InvalidSlot(slot));
+        }
+        self.check_not_executing(slot)?;
+        self.load = slot;
+        Ok(())
+    }
+
+    /// The slot applications are loaded into
+    pub fn load_slot(&self) -> usize {
+        self.load
+    }
+
+    /// Switch the slot that is executed, pausing the application in the current slot
+    pub fn switch_slot(&mut self, slot: usize) -> Result<(), Error> {
+        if slot >= self.slots.len() {
+            return Err(Error::InvalidSlot(slot));
+        }
+        self.pause();
+        self.active = slot;
+        Ok(())
+    }
+
+    /// The slot that is executed
+    pub fn active_slot(&self) -> usize {
+        self.active
+    }
+
+    /// Set a handler to be called with the total number of bytes stored, each time a byte is written into ram
+    pub fn set_progress_handler(&mut self, f: fn(bytes_written: usize)) {
+        self.progress_fn = Some(f);
+    }
+
+    /// Fails with `Error::Executing` if `slot` is the active slot and its application is running
+    fn check_not_executing(&self, slot: usize) -> Result<(), Error> {
+        if slot == self.active && self.slots[slot].status.is_running {
+            return Err(Error::Executing);
+        }
+        Ok(())
+    }
+
+    /// The slot being loaded, unless its application is executing
+    fn loading(&mut self) -> Result<&mut Slot, Error> {
+        let slot = &mut self.slots[self.load];
+        if slot.status.is_running {
+            return Err(Error::Executing);
+        }
+        Ok(slot)
+    }
+
+    /// Write a byte into the ram of the load slot
     pub fn write_ram_byte(&mut self, byte: u8) -> Result<(), Error> {
-        self.ram.write(byte)?;
+        let slot = self.loading()?;
+        if let Err(err) = slot.ram.write(byte) {
+            slot.overflowed = true;
+            return Err(err);
+        }
+        slot.status.ram_used += 1;
+        slot.received = slot.ram.written();
+        let ram_used = slot.status.ram_used;
+        if let Some(progress_fn) = self.progress_fn {
+            progress_fn(ram_used);
+        }
         Ok(())
     }
 
-    /// Write a checksum byte into the manager internal cs buffer
+    /// Write a run of bytes into the load slots ram, the bytes that fit are written even if the run overflows
+    pub fn write_ram(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let slot = self.loading()?;
+        let mut result = Ok(());
+        for byte in bytes {
+            if let Err(err) = slot.ram.write(*byte) {
+                slot.overflowed = true;
+                result = Err(err);
+                break;
+            }
+            slot.status.ram_used += 1;
+        }
+        slot.received = slot.ram.written();
+        let ram_used = slot.status.ram_used;
+        if let Some(progress_fn) = self.progress_fn {
+            progress_fn(ram_used);
+        }
+        result
+    }
+
+    /// Write a run of bytes into the load slots ram starting at `offset`, so a chunked transfer can be resumed or
+    /// sent out of order. The image ends at the furthest byte written
+    pub fn write_ram_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Error> {
+        let slot = self.loading()?;
+        let result = slot.ram.write_at(offset, bytes);
+        if result.is_err() {
+            slot.overflowed = true;
+        }
+        if offset <= slot.received {
+            slot.received = slot.received.max(offset.saturating_add(bytes.len()).min(slot.ram.written()));
+        }
+        slot.status.ram_used = slot.ram.written();
+        let ram_used = slot.status.ram_used;
+        if let Some(progress_fn) = self.progress_fn {
+            progress_fn(ram_used);
+        }
+        result
+    }
+
+    /// Write a byte into the load slots ram at `offset`, see `write_ram_at`
+    pub fn write_ram_byte_at(&mut self, offset: usize, byte: u8) -> Result<(), Error> {
+        self.write_ram_at(offset, &[byte])
+    }
+
+    /// The length of the image in the load slot received without a gap from its start, so a chunked transfer can
+    /// always be resumed from this offset. Chunks received past a gap are only counted once the gap is filled
+    /// by a chunk that reaches them, so it may be resent more than is needed
+    pub fn received(&self) -> usize {
+        self.slots[self.load].received
+    }
+
+    /// The checksum of the load slot, if it has been fully received
+    pub fn staged_checksum(&self) -> Option<u32> {
+        let slot = &self.slots[self.load];
+        if slot.target_cs_idx == CHECKSUM_LEN {
+            Some(ApplicationManager::digest_from_bytes(&slot.target_cs))
+        } else {
+            None
+        }
+    }
+
+    /// Discard the checksum of the load slot so another can be written, keeping its ram
+    pub fn clear_checksum(&mut self) -> Result<(), Error> {
+        let slot = self.loading()?;
+        slot.target_cs_idx = 0;
+        slot.status.is_loaded = false;
+        Ok(())
+    }
+
+    /// Write a checksum byte into the load slots internal cs buffer
     pub fn write_checksum_byte(&mut self, byte: u8) -> Result<(), Error> {
-        if self.target_cs_idx > self.target_cs.len() {
+        let slot = self.loading()?;
+        if slot.target_cs_idx >= slot.target_cs.len() {
             Err(Error::NoMemory)
         } else {
-            self.target_cs[self.target_cs_idx] = byte;
-            self.target_cs_idx += 1;
+            slot.target_cs[slot.target_cs_idx] = byte;
+            slot.target_cs_idx += 1;
             Ok(())
         }
     }
 
-    /// Verify the contents of ram using a crc against the checksum
+    /// Write a byte of the application name into the load slot
+    pub fn write_name_byte(&mut self, byte: u8) -> Result<(), Error> {
+        let slot = self.loading()?;
+        ApplicationManager::push_metadata(&mut slot.name, byte)
+    }
+
+    /// Write a byte of the application version into the load slot
+    pub fn write_version_byte(&mut self, byte: u8) -> Result<(), Error> {
+        let slot = self.loading()?;
+        ApplicationManager::push_metadata(&mut slot.version, byte)
+    }
+
+    fn push_metadata(s: &mut String<U16>, byte: u8) -> Result<(), Error> {
+        if !byte.is_ascii() {
+            return Err(Error::InvalidMetadata);
+        }
+        s.push(byte as char).map_err(|_| Error::InvalidMetadata)
+    }
+
+    /// The name of the application in the active slot, if it was sent
+    pub fn name(&self) -> Option<&str> {
+        let name = self.slots[self.active].name.as_str();
+        if name.is_empty() { None } else { Some(name) }
+    }
+
+    /// The version of the application in the active slot, if it was sent
+    pub fn version(&self) -> Option<&str> {
+        let version = self.slots[self.active].version.as_str();
+        if version.is_empty() { None } else { Some(version) }
+    }
+
+    /// The number of checksum bytes written since the load slot was last killed
+    pub fn checksum_written(&self) -> usize {
+        self.slots[self.load].target_cs_idx
+    }
+
+    /// Verify the contents of the load slots ram using a crc against the checksum
     pub fn verify(&mut self) -> Result<(), Error> {
-       let ram_cs = self.ram.cs();
-       let digest = ApplicationManager::digest_from_bytes(&self.target_cs); 
+        let slot = &mut self.slots[self.load];
+        if slot.target_cs_idx != CHECKSUM_LEN {
+            return Err(Error::NotStaged);
+        }
+        if slot.overflowed {
+            return Err(Error::TooLarge);
+        }
+        if slot.status.ram_used == 0 {
+            return Err(Error::EmptyImage);
+        }
+        let ram_cs = slot.ram.cs();
+        let digest = ApplicationManager::digest_from_bytes(&slot.target_cs); 
         info!("Current Ram Digest: {}, stored ram Digest: {}", ram_cs, digest);
         if digest == ram_cs {
-            self.status.is_loaded = true;
+            slot.status.is_loaded = true;
             Ok(())
         } else {
             error!("Application checksum failed!");
-            Err(Error::ChecksumFailed)
+            Err(Error::ChecksumMismatch { expected: digest, actual: ram_cs })
         }
     }
 
@@ -113,35 +380,70 @@ impl ApplicationManager {
         digest
     }
 
-    /// Run the application
+    /// Run the application in the active slot
     pub fn execute(&mut self) -> Result<(), Error> {
-        if !self.status.is_loaded {
+        let slot = &mut self.slots[self.active];
+        if !slot.status.is_loaded {
             return Err(Error::NoApplication);
         }
-        let setup_ptr = Self::fn_ptr_from_slice(&self.ram.as_ref()[..4]);
-        let service_ptr = Self::fn_ptr_from_slice(&self.ram.as_ref()[4..8]);
-        let input_ptr = Self::fn_ptr_from_slice(&self.ram.as_ref()[8..12]);
+        let setup_ptr = Self::fn_ptr_from_slice(&slot.ram.as_ref()[..4]);
+        let service_ptr = Self::fn_ptr_from_slice(&slot.ram.as_ref()[4..8]);
+        let input_ptr = Self::fn_ptr_from_slice(&slot.ram.as_ref()[8..12]);
         let _result = unsafe {
             let setup: SetupFn = ::core::mem::transmute(setup_ptr);
             let service: ServiceFn = ::core::mem::transmute(service_ptr);
             let input: InputFn = ::core::mem::transmute(input_ptr);
-            self.service_fn = Some(service);
-            self.input_fn = Some(input);
+            slot.service_fn = Some(service);
+            slot.input_fn = Some(input);
             setup()
         };
-        self.status.is_running = true;
+        slot.status.is_running = true;
+        self.last_pet_ms = self.now_ms;
+        Ok(())
+    }
+
+    /// Kill the running application if it does not pet the watchdog (see `types::pet_watchdog`) at least every `ms`.
+    ///
+    /// The watchdog is checked by `tick`, so it cannot interrupt an application that never returns.
+    /// Disabled by default, pass `None` to disable.
+    pub fn set_exec_timeout(&mut self, ms: Option<u32>) {
+        self.exec_timeout_ms = ms;
+        self.last_pet_ms = self.now_ms;
+    }
+
+    /// Reset the watchdog of the running application
+    pub fn pet_watchdog(&mut self) {
+        self.last_pet_ms = self.now_ms;
+    }
+
+    /// Update the managers notion of time, killing the running application if the watchdog has expired
+    pub fn tick(&mut self, now_ms: u32) -> Result<(), Error> {
+        self.now_ms = now_ms;
+        if self.petted {
+            self.petted = false;
+            self.pet_watchdog();
+        }
+        if let Some(timeout) = self.exec_timeout_ms {
+            if self.status().is_running && now_ms.wrapping_sub(self.last_pet_ms) >= timeout {
+                error!("Application did not pet the watchdog within {}ms, killing it", timeout);
+                self.kill()?;
+                return Err(Error::WatchdogTimeout);
+            }
+        }
         Ok(())
     }
 
 
     /// Gives processing time to the application
     pub fn service(&mut self, display: &mut Ssd1351) -> Result<(), Error> {
-       if let Some(service_fn) = self.service_fn {
+       let slot = &mut self.slots[self.active];
+       if let Some(service_fn) = slot.service_fn {
         let mut ctx = Context {
             display: Some(display),
             log: application_logger,
+            watchdog: Some(&mut self.petted),
         };
-        self.status.service_result = service_fn(&mut ctx);
+        slot.status.service_result = service_fn(&mut ctx);
         Ok(())
        } else {
            Err(Error::InvalidServiceFn)
@@ -150,11 +452,12 @@ impl ApplicationManager {
 
     /// Gives processing time to input handlers of the function
     pub fn service_input(&mut self, input: InputEvent) -> Result<(), Error> {
-       if let Some(input_fn) = self.input_fn {
+       if let Some(input_fn) = self.slots[self.active].input_fn {
         let mut ctx = Context {
             // display is only passed in on update, not on input
             display: None,
             log: application_logger,
+            watchdog: Some(&mut self.petted),
         };
         let _ = input_fn(&mut ctx, input);
         Ok(())
@@ -165,23 +468,29 @@ impl ApplicationManager {
 
     /// Pause the application
     pub fn pause(&mut self) {
-        self.status.is_running = false;
+        self.slots[self.active].status.is_running = false;
     }
 
-    /// Kill the current application and unload from memory
+    /// Kill the current application and unload from memory, even if it is running
     pub fn kill(&mut self) -> Result<(), Error> {
-        self.ram.reset();
-        self.target_cs_idx = 0;
-        self.status.is_loaded = false;
-        self.status.is_running = false;
-        self.input_fn = None;
-        self.service_fn = None;
+        self.slots[self.active].kill();
+        Ok(())
+    }
+
+    /// Kill the application in `slot` and unload it from memory, the running application can only be stopped
+    /// with `kill`
+    pub fn kill_slot(&mut self, slot: usize) -> Result<(), Error> {
+        if slot >= self.slots.len() {
+            return Err(Error::InvalidSlot(slot));
+        }
+        self.check_not_executing(slot)?;
+        self.slots[slot].kill();
         Ok(())
     }
 
-    /// Return the status of the manager
+    /// Return the status of the active slot
     pub fn status(&self) -> Status {
-        self.status
+        self.slots[self.active].status
     }
 
     /// convert 4 byte slice into a const ptr
@@ -216,7 +525,7 @@ impl Ram {
 
     /// Write a byte into Ram
     pub fn write(&mut self, byte: u8) -> Result<(), Error> {
-        if self.ram_idx > self.ram.len() {
+        if self.ram_idx >= self.ram.len() {
             Err(Error::NoMemory)
         } else {
             self.ram[self.ram_idx] = byte;
@@ -225,6 +534,27 @@ impl Ram {
         }
     }
 
+    /// Write `bytes` into Ram at `offset`, extending the written length if they are past the end.
+    /// The bytes that fit are written even if the run overflows
+    pub fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Error> {
+        let end = offset.saturating_add(bytes.len());
+        let fits = end.min(self.ram.len());
+        if offset < fits {
+            self.ram[offset..fits].copy_from_slice(&bytes[..fits - offset]);
+            self.ram_idx = self.ram_idx.max(fits);
+        }
+        if fits < end {
+            Err(Error::NoMemory)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The number of bytes written since the last reset
+    pub fn written(&self) -> usize {
+        self.ram_idx
+    }
+
     /// ieee crc32 of the ram buffer
     pub fn cs(&self) -> u32 {
         checksum_ieee(&self.ram[..self.ram_idx])
@@ -255,11 +585,178 @@ extern "C" fn application_logger(string: &str) -> i32 {
     0
 }
 
+#[cfg(test)]
+impl ApplicationManager {
+    /// Marks the application in the active slot as running, as `execute` would
+    pub(crate) fn set_running(&mut self, running: bool) {
+        self.slots[self.active].status.is_running = running;
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::cell::RefCell;
+    use std::vec::Vec;
+
     #[test]
     fn checksum_parsing_works() {
         assert_eq!(ApplicationManager::digest_from_bytes(&[35, 98, 167, 98]), 0x2362A762);
     }
-}
\ No newline at end of file
+
+    thread_local!(static PROGRESS: RefCell<Vec<usize>> = RefCell::new(Vec::new()));
+
+    fn progress(bytes_written: usize) {
+        PROGRESS.with(|p| p.borrow_mut().push(bytes_written));
+    }
+
+    #[test]
+    fn progress_handler_works() {
+        let ram: &'static mut [u8] = Box::leak(vec![0u8; 64].into_boxed_slice());
+        let mut am = ApplicationManager::new(Ram::new(ram));
+        am.set_progress_handler(progress);
+        for byte in 0..10 {
+            am.write_ram_byte(byte).unwrap();
+        }
+        PROGRESS.with(|p| {
+            assert_eq!(*p.borrow(), (1..=10).collect::<Vec<usize>>());
+        });
+        assert_eq!(am.status().ram_used, 10);
+
+        // loading a new application resets the progress
+        am.kill().unwrap();
+        assert_eq!(am.status().ram_used, 0);
+    }
+
+    /// Load `image` into the load slot
+    fn load(am: &mut ApplicationManager, image: &[u8]) {
+        am.kill_slot(am.load_slot()).unwrap();
+        for byte in checksum_ieee(image).to_be_bytes().iter() {
+            am.write_checksum_byte(*byte).unwrap();
+        }
+        for byte in image {
+            am.write_ram_byte(*byte).unwrap();
+        }
+        am.verify().unwrap();
+    }
+
+    #[test]
+    fn slots() {
+        let home: &'static mut [u8] = Box::leak(vec![0u8; 64].into_boxed_slice());
+        let side: &'static mut [u8] = Box::leak(vec![0u8; 64].into_boxed_slice());
+        let mut am = ApplicationManager::new(Ram::new(home));
+        assert_eq!(am.add_slot(Ram::new(side)), Ok(1));
+        assert_eq!(am.slot_count(), 2);
+
+        load(&mut am, &[1, 2, 3, 4]);
+        am.select_slot(1).unwrap();
+        load(&mut am, &[5, 6]);
+        assert_eq!(am.status().ram_used, 4);
+        am.switch_slot(1).unwrap();
+        assert_eq!(am.active_slot(), 1);
+        assert_eq!(am.status().ram_used, 2);
+        assert!(am.status().is_loaded);
+        assert_eq!(am.switch_slot(2), Err(Error::InvalidSlot(2)));
+        assert_eq!(am.select_slot(2), Err(Error::InvalidSlot(2)));
+
+        // the executing slot cannot be written
+        am.slots[1].status.is_running = true;
+        assert_eq!(am.write_ram_byte(0), Err(Error::Executing));
+        // nor selected or unloaded, except by `kill`
+        assert_eq!(am.select_slot(1), Err(Error::Executing));
+        assert_eq!(am.kill_slot(1), Err(Error::Executing));
+        assert!(am.slots[1].status.is_loaded);
+        am.select_slot(0).unwrap();
+        assert_eq!(am.write_ram_byte(0), Ok(()));
+
+        // switching pauses the running application, leaving it loaded
+        am.switch_slot(0).unwrap();
+        assert!(!am.slots[1].status.is_running);
+        assert!(am.slots[1].status.is_loaded);
+    }
+
+    #[test]
+    fn write_at_offsets() {
+        let ram: &'static mut [u8] = Box::leak(vec![0u8; 8].into_boxed_slice());
+        let mut am = ApplicationManager::new(Ram::new(ram));
+        for byte in checksum_ieee(&[1, 2, 3, 4, 5, 6]).to_be_bytes().iter() {
+            am.write_checksum_byte(*byte).unwrap();
+        }
+        am.write_ram_at(4, &[5, 6]).unwrap();
+        assert_eq!(am.status().ram_used, 6);
+        assert_eq!(am.received(), 0);
+        am.write_ram_at(0, &[1, 2]).unwrap();
+        assert_eq!(am.received(), 2);
+        am.write_ram_at(2, &[3, 4]).unwrap();
+        assert_eq!(am.status().ram_used, 6);
+        // the chunk past the gap was not counted
+        assert_eq!(am.received(), 4);
+        am.write_ram_at(2, &[3, 4, 5, 6]).unwrap();
+        assert_eq!(am.received(), 6);
+        assert_eq!(am.verify(), Ok(()));
+
+        // a rewritten checksum keeps the image
+        am.clear_checksum().unwrap();
+        assert_eq!(am.staged_checksum(), None);
+        for byte in checksum_ieee(&[1, 2, 3, 4, 5, 6]).to_be_bytes().iter() {
+            am.write_checksum_byte(*byte).unwrap();
+        }
+        assert_eq!(am.staged_checksum(), Some(checksum_ieee(&[1, 2, 3, 4, 5, 6])));
+
+        assert_eq!(am.write_ram_byte_at(7, 8), Ok(()));
+        assert_eq!(am.write_ram_at(7, &[8, 9]), Err(Error::NoMemory));
+        assert_eq!(am.verify(), Err(Error::TooLarge));
+    }
+
+    #[test]
+    fn watchdog_kills_hung_application() {
+        let ram: &'static mut [u8] = Box::leak(vec![0u8; 64].into_boxed_slice());
+        let mut am = ApplicationManager::new(Ram::new(ram));
+        load(&mut am, &[1, 2, 3, 4]);
+        am.set_exec_timeout(Some(100));
+        am.slots[0].status.is_running = true;
+
+        assert_eq!(am.tick(50), Ok(()));
+        am.petted = true; // as the application would through its context
+        assert_eq!(am.tick(90), Ok(()));
+        assert_eq!(am.tick(180), Ok(()));
+        am.pet_watchdog();
+        assert_eq!(am.tick(270), Ok(()));
+
+        // hung, no more pets
+        assert_eq!(am.tick(370), Err(Error::WatchdogTimeout));
+        assert!(!am.status().is_running);
+        assert!(!am.status().is_loaded);
+        assert_eq!(am.tick(1000), Ok(()));
+    }
+
+    #[test]
+    fn verify_errors() {
+        let ram: &'static mut [u8] = Box::leak(vec![0u8; 4].into_boxed_slice());
+        let mut am = ApplicationManager::new(Ram::new(ram));
+        assert_eq!(am.verify(), Err(Error::NotStaged));
+
+        for byte in checksum_ieee(&[1, 2, 3, 4]).to_be_bytes().iter().take(2) {
+            am.write_checksum_byte(*byte).unwrap();
+        }
+        assert_eq!(am.verify(), Err(Error::NotStaged));
+
+        am.kill().unwrap();
+        for byte in checksum_ieee(&[1, 2, 3, 4]).to_be_bytes().iter() {
+            am.write_checksum_byte(*byte).unwrap();
+        }
+        assert_eq!(am.verify(), Err(Error::EmptyImage));
+
+        for byte in &[1, 2, 3, 5] {
+            am.write_ram_byte(*byte).unwrap();
+        }
+        assert_eq!(am.verify(), Err(Error::ChecksumMismatch {
+            expected: checksum_ieee(&[1, 2, 3, 4]),
+            actual: checksum_ieee(&[1, 2, 3, 5]),
+        }));
+
+        assert_eq!(am.write_ram_byte(6), Err(Error::NoMemory));
+        assert_eq!(am.verify(), Err(Error::TooLarge));
+        assert!(!am.status().is_loaded);
+    }
+}
@@ -88,6 +88,11 @@ impl DisplayManager
 
     /// Services input to the current application
     pub fn service_input(&mut self, system: &mut System, input: InputEvent) {
+        if input == InputEvent::ChargingStarted {
+            // the clock shows the charging state
+            self.handle_exit(Signal::Home);
+            return;
+        }
         let signal = match self.state_idx {
             0 => {
                 DisplayManager::static_state_input(&mut self.clock_state, system, input)
@@ -54,7 +54,9 @@ impl ScopedState for AppState {
     fn preview(&mut self, system: &mut System, display: &mut Ssd1351) -> Option<Signal> {
         self.buffer.clear();
         let status = system.am().status();
-        if status.is_loaded {
+        if let (true, Some(name)) = (status.is_loaded, system.am().name()) {
+            write!(self.buffer, "Open {}", name).unwrap();
+        } else if status.is_loaded {
             write!(self.buffer, "Open loaded App").unwrap();
         } else {
             write!(self.buffer, "No App loaded!").unwrap();
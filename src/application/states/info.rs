@@ -49,7 +49,8 @@ impl State for InfoState {
                 .into_iter(),
         );
         self.buffer.clear();
-        write!(self.buffer, "TSC THRES: {}", system.ss().tsc_threshold).unwrap();
+        let thresholds = system.ss().tsc_threshold;
+        write!(self.buffer, "TSC THRES: {} {} {}", thresholds[0], thresholds[1], thresholds[2]).unwrap();
         display.draw(
             Font6x12::render_str(self.buffer.as_str())
                 .translate(Coord::new(0, 48))
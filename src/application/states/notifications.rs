@@ -47,7 +47,7 @@ impl State for NotificationState {
                             .into_iter(),
                     );
                     for item in 0..system.nm().idx() {
-                        system.nm().peek_notification(item, |notification| {
+                        system.nm().peek_by_priority(item, |notification| {
                             display.draw(horizontal_centre(Font6x12::render_str(notification.title()), item as i32 * CHAR_HEIGHT)
                                     .with_stroke(Some(0x02D4_u16.into()))
                                     .into_iter(),
@@ -62,7 +62,7 @@ impl State for NotificationState {
                 }
             },
             InternalState::Body => {
-                system.nm().peek_notification(self.menu.selected() as usize, |notification| {
+                system.nm().peek_by_priority(self.menu.selected() as usize, |notification| {
                     self.body.render(display, &notification);
                 });
             }
@@ -89,7 +89,10 @@ impl State for NotificationState {
                         },
                         InputEvent::Middle => {
                             self.state = InternalState::Body;
-                            system.nm().peek_notification(self.menu.selected() as usize, |notification| {
+                            if let Some(index) = system.nm().priority_index(self.menu.selected() as usize) {
+                                system.nm().mark_read(index);
+                            }
+                            system.nm().peek_by_priority(self.menu.selected() as usize, |notification| {
                                 let line_count = notification.body().len() as i32 / LINE_WIDTH;
                                 self.body =  Body::new(line_count - line_count / 2);
                             });
@@ -0,0 +1,26 @@
+//! Defmt logging
+//!
+//! Routes the `log` style macros used throughout the kernel to `defmt` when the `defmt` feature is enabled.
+//! The arguments are formatted through their `Debug` implementations, so they need not implement `defmt::Format`.
+
+#[allow(unused_macros)]
+macro_rules! trace {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => { defmt::trace!($fmt $(, defmt::Debug2Format(&($arg)))*) };
+}
+
+#[allow(unused_macros)]
+macro_rules! debug {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => { defmt::debug!($fmt $(, defmt::Debug2Format(&($arg)))*) };
+}
+
+macro_rules! info {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => { defmt::info!($fmt $(, defmt::Debug2Format(&($arg)))*) };
+}
+
+macro_rules! warn {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => { defmt::warn!($fmt $(, defmt::Debug2Format(&($arg)))*) };
+}
+
+macro_rules! error {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => { defmt::error!($fmt $(, defmt::Debug2Format(&($arg)))*) };
+}
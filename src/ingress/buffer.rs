@@ -3,13 +3,23 @@
 //! A thin abstraction over a static array, with some meta data
 
 use crate::system::notification::BUFF_SIZE;
+use core::str::Utf8Error;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Type {
     Unknown, /* NULL */
     Notification,
     Syscall,
     Application,
+    NotificationDismiss,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The buffer has no space left
+    Full,
 }
 
 #[derive(Copy, Clone)]
@@ -17,6 +27,8 @@ pub struct Buffer {
     pub btype: Type,
     pub payload: [u8; BUFF_SIZE],
     pub payload_idx: usize,
+    /// Set when a write was rejected because the buffer was full
+    pub truncated: bool,
 }
 
 impl Default for Buffer {
@@ -26,6 +38,7 @@ impl Default for Buffer {
             btype: Type::Unknown,
             payload: [0u8; BUFF_SIZE],
             payload_idx: 0,
+            truncated: false,
         }
     }
 }
@@ -37,6 +50,7 @@ impl Buffer {
             btype: Type::Unknown,
             payload: rx_buffer,
             payload_idx: 0,
+            truncated: false,
         }
     }
 
@@ -45,20 +59,74 @@ impl Buffer {
         self.btype
     }
 
-    /// Writes a byte into the buffer
-    pub fn write(&mut self, byte: u8) {
+    /// Writes a byte into the buffer, marking it as truncated if it is full
+    pub fn write(&mut self, byte: u8) -> Result<(), Error> {
+        if self.remaining() == 0 {
+            self.truncated = true;
+            return Err(Error::Full);
+        }
         self.payload[self.payload_idx] = byte;
         self.payload_idx += 1;
+        Ok(())
+    }
+
+    /// The number of bytes the buffer can hold
+    pub const fn capacity(&self) -> usize {
+        BUFF_SIZE
+    }
+
+    /// The number of bytes written since the last clear
+    pub const fn len(&self) -> usize {
+        self.payload_idx
+    }
+
+    /// Whether nothing has been written since the last clear
+    pub const fn is_empty(&self) -> bool {
+        self.payload_idx == 0
+    }
+
+    /// The number of bytes that can be written before the buffer is full
+    pub const fn remaining(&self) -> usize {
+        BUFF_SIZE - self.payload_idx
+    }
+
+    /// Writes a run of bytes into the buffer, the bytes that fit are written even if the run overflows
+    pub fn write_slice(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let len = bytes.len().min(self.remaining());
+        self.payload[self.payload_idx..self.payload_idx + len].copy_from_slice(&bytes[..len]);
+        self.payload_idx += len;
+        if len < bytes.len() {
+            self.truncated = true;
+            return Err(Error::Full);
+        }
+        Ok(())
+    }
+
+    /// Whether any bytes were dropped since the last clear
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
     }
 
     // Resets the index of the buffer, does not blank the memory
     pub fn clear(&mut self) {
         self.payload_idx = 0;
+        self.truncated = false;
+    }
+
+    /// The sections of the buffer, `nsi` being the end index of each section (as recorded by the ingress manager
+    /// for notifications, i.e source, title and body). Iteration stops at an index past the written bytes
+    pub fn sections<'a>(&'a self, nsi: &'a [usize]) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let payload = &self.payload[..self.payload_idx];
+        nsi.iter().scan(0, move |start, &end| {
+            let section = payload.get(*start..end)?;
+            *start = end;
+            Some(section)
+        })
     }
 
-    /// Buffer as &str
-    pub fn as_str(&self) -> &str {
-        unsafe { core::str::from_utf8_unchecked(&self.payload[0..self.payload_idx]) }
+    /// Buffer as &str, fails if the written bytes are not valid UTF-8
+    pub fn as_str(&self) -> Result<&str, Utf8Error> {
+        core::str::from_utf8(&self.payload[0..self.payload_idx])
     }
 }
 
@@ -83,3 +151,53 @@ impl core::fmt::Display for Buffer {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn len_and_remaining() {
+        let mut buffer = Buffer::default();
+        assert_eq!(buffer.capacity(), BUFF_SIZE);
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.remaining(), BUFF_SIZE);
+        for len in 1..=BUFF_SIZE {
+            buffer.write(b'x').unwrap();
+            assert_eq!(buffer.len(), len);
+            assert_eq!(buffer.remaining(), BUFF_SIZE - len);
+        }
+        assert_eq!(buffer.write(b'x'), Err(Error::Full));
+        assert_eq!(buffer.len(), BUFF_SIZE);
+        assert_eq!(buffer.remaining(), 0);
+
+        buffer.clear();
+        assert_eq!(buffer.remaining(), BUFF_SIZE);
+    }
+
+    #[test]
+    fn sections() {
+        let mut buffer = Buffer::default();
+        for byte in "srctitlebody".bytes() {
+            buffer.write(byte).unwrap();
+        }
+        let sections: std::vec::Vec<&[u8]> = buffer.sections(&[3, 8, 12]).collect();
+        assert_eq!(sections, [&b"src"[..], b"title", b"body"]);
+
+        assert_eq!(buffer.sections(&[3, 3, 12]).nth(1), Some(&b""[..]));
+        assert_eq!(buffer.sections(&[3, 20]).count(), 1);
+    }
+
+    #[test]
+    fn as_str_invalid_utf8() {
+        let mut buffer = Buffer::default();
+        for byte in "tïme".bytes() {
+            buffer.write(byte).unwrap();
+        }
+        assert_eq!(buffer.as_str(), Ok("tïme"));
+
+        buffer.write(0xFF).unwrap();
+        let err = buffer.as_str().unwrap_err();
+        assert_eq!(err.valid_up_to(), "tïme".len());
+    }
+}
@@ -0,0 +1,98 @@
+//! Encode
+//!
+//! Frames packets the way the ingress manager expects them, for hosts talking to the watch and for tests.
+//! Each function writes the framed, escaped packet into `out`, returning the number of bytes written.
+
+use crate::ingress::ingress_manager::{FramingConfig, IngressError};
+
+/// Frame a packet of type `btype`, each section being preceded by a separator
+pub fn encode(btype: u8, sections: &[&[u8]], out: &mut [u8]) -> Result<usize, IngressError> {
+    let framing = FramingConfig::default();
+    let mut idx = 0;
+    push(out, &mut idx, framing.stx)?;
+    push(out, &mut idx, btype)?;
+    for section in sections {
+        push(out, &mut idx, framing.sep)?;
+        idx += framing.escape_into(section, &mut out[idx..])?;
+    }
+    push(out, &mut idx, framing.etx)?;
+    Ok(idx)
+}
+
+/// Frame a syscall, i.e `"t"` to get the time
+pub fn encode_syscall(syscall: &str, out: &mut [u8]) -> Result<usize, IngressError> {
+    encode(b'S', &[syscall.as_bytes()], out)
+}
+
+/// Frame a notification
+pub fn encode_notification(source: &str, title: &str, body: &str, out: &mut [u8]) -> Result<usize, IngressError> {
+    encode(b'N', &[source.as_bytes(), title.as_bytes(), body.as_bytes()], out)
+}
+
+/// Frame an application as raw bytes, `checksum` being the ieee crc32 of the image
+pub fn encode_application(checksum: u32, image: &[u8], out: &mut [u8]) -> Result<usize, IngressError> {
+    encode(b'a', &[&checksum.to_be_bytes(), image], out)
+}
+
+fn push(out: &mut [u8], idx: &mut usize, byte: u8) -> Result<(), IngressError> {
+    *out.get_mut(*idx).ok_or(IngressError::BufferFull)? = byte;
+    *idx += 1;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ingress::buffer::Type;
+    use crate::ingress::ingress_manager::IngressManager;
+    use crate::system::system::System;
+    use crc::crc32::checksum_ieee;
+
+    fn decode(data: &[u8], system: &mut System) -> Option<Type> {
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(data).unwrap();
+        imgr.process(system).unwrap()
+    }
+
+    #[test]
+    fn syscall_round_trip() {
+        let mut system = System::mock();
+        let mut out = [0u8; 16];
+        let len = encode_syscall("B40", &mut out).unwrap();
+        assert_eq!(decode(&out[..len], &mut system), Some(Type::Syscall));
+        assert_eq!(system.brightness(), 40);
+    }
+
+    #[test]
+    fn notification_round_trip() {
+        let mut system = System::mock();
+        let mut out = [0u8; 64];
+        // includes a separator, which must be escaped
+        let len = encode_notification("src", "ti\x1Ftle", "body", &mut out).unwrap();
+        assert_eq!(decode(&out[..len], &mut system), Some(Type::Notification));
+        system.nm().peek_notification(0, |notification| {
+            assert_eq!(notification.source(), "src");
+            assert_eq!(notification.title(), "ti\x1Ftle");
+            assert_eq!(notification.body(), "body");
+        });
+    }
+
+    #[test]
+    fn application_round_trip() {
+        let mut system = System::mock();
+        let image = [2, 3, 27, 31, 0xFF, 0x00, b'A'];
+        let mut out = [0u8; 32];
+        let len = encode_application(checksum_ieee(&image), &image, &mut out).unwrap();
+        assert_eq!(decode(&out[..len], &mut system), Some(Type::Application));
+        let status = system.am().status();
+        assert!(status.is_loaded);
+        assert_eq!(status.ram_used, image.len());
+    }
+
+    #[test]
+    fn encode_too_small() {
+        let mut out = [0u8; 5];
+        assert_eq!(encode_syscall("t", &mut out), Ok(5));
+        assert_eq!(encode_syscall("tt", &mut out), Err(IngressError::BufferFull));
+    }
+}
@@ -5,107 +5,844 @@
 use crate::ingress::buffer::{Buffer, Type};
 use heapless::consts::*;
 use heapless::spsc::Queue;
+use heapless::{ArrayLength, Vec};
 use simple_hex::hex_byte_to_byte;
 use crate::system::system::System;
-use crate::system::syscall::Syscall;
+use crate::application::application_manager::{CHECKSUM_LEN, Error as AmError};
+use crate::system::syscall::{Syscall, SyscallResponse};
+use crate::system::notification::{NotificationError, DEFAULT_PRIORITY};
+use crate::system::haptic::{Pattern, NOTIFICATION_BUZZ_MS};
 use core::str::FromStr;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 enum State {
     /// Waiting for a STX byte, or just received an ETX, or entered an invalid state
     Wait,
     /// Init state, just after receiving and STX
     Init,
+    /// Optional packet flags, just after the type byte
+    Flags,
+    /// Parse the length of the frame, only sent with the length flag
+    FrameLength,
     /// Write into an internal buffer for parsing
     Payload,
 
+    /// Parse the application name, only sent with the metadata flag
+    ApplicationName,
+    /// Parse the application version, only sent with the metadata flag
+    ApplicationVersion,
     /// Parse the application checksum
     ApplicationChecksum,
+    /// Parse the offset of an application chunk, only sent with the chunk flags
+    ApplicationOffset,
     /// Store the application in ram
     ApplicationStore,
 
+
+    /// Notification timestamp, the unix time it was sent, only sent with the timestamp flag
+    NotificationTimestamp,
     /// Notification Source - what generated the push notification
     NotificationSource,
     /// Notification title
     NotificationTitle,
     /// Notification body
     NotificationBody,
+
+    /// Capture a frame of unknown type verbatim, see `IngressManager::set_capture_unknown`
+    Capture,
+}
+
+/// Why a frame was dropped, see `MatchResult::Aborted`
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum AbortReason {
+    /// The type byte was not recognised
+    UnknownType,
+    /// The frame did not match its CRC
+    CrcMismatch,
+    /// The frame had no CRC, but one is required
+    CrcMissing,
+    /// A section was not valid UTF-8
+    InvalidUtf8,
+    /// An application image was not valid hex, or could not be staged
+    InvalidImage,
+    /// An application could not be stored
+    Application(AmError),
+    /// The frame exceeded the maximum frame size
+    TooLong,
+    /// The payload could not be parsed or stored
+    Invalid,
+}
+
+/// The result of running the ring buffer through the state machine
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum MatchResult {
+    /// The ring buffer was drained without completing a frame
+    Incomplete,
+    /// A frame was completed, `Type::Unknown` being a frame of unknown type captured for debugging
+    Completed(Type),
+    /// A frame was ended, but its payload was dropped
+    Aborted(AbortReason),
+}
+
+/// The publically observable state of the `IngressManager`, see `IngressManager::state()`
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum IngressState {
+    Wait,
+    Init,
+    Flags,
+    FrameLength,
+    Payload,
+    ApplicationName,
+    ApplicationVersion,
+    ApplicationChecksum,
+    ApplicationOffset,
+    ApplicationStore,
+    NotificationTimestamp,
+    NotificationSource,
+    NotificationTitle,
+    NotificationBody,
+    Capture,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IngressError {
+    /// The internal ring buffer is full, no more bytes can be accepted
+    BufferFull,
+    /// The received application did not match its checksum
+    ChecksumMismatch,
+    /// A notification section or syscall was not valid UTF-8
+    InvalidUtf8,
+    /// The received application could not be loaded
+    InvalidApplication(AmError),
+    /// An application image was not valid hex, or could not be staged
+    InvalidImage,
+    /// The frame exceeded the maximum frame size before its ETX
+    FrameTooLong,
+}
+
+/// Counters of ingress failures, useful for debugging links in the field
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct IngressStats {
+    /// Packets received intact, whether or not they could then be processed
+    pub packets_ok: u32,
+    /// Packets dropped because their type could not be determined
+    pub unknown_type: u32,
+    /// Packets abandoned part way through by the start of a new packet
+    pub partial_resets: u32,
+    /// Packets that failed a CRC or checksum verification
+    pub checksum_errors: u32,
+    /// Writes that lost bytes because the ring buffer was full, however many bytes were lost
+    pub overflow_drops: u32,
+    /// Frames dropped for exceeding the maximum frame size, see `IngressManager::set_max_frame`
+    pub frames_too_long: u32,
 }
 
+/// A framed `ACK` or `NACK` to send back to the sender, see `IngressManager::process_with_ack`
+pub type Response = [u8; 4];
+
+/// Why a packet was rejected, sent after a `NACK`
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum NackCode {
+    /// The type byte was not recognised
+    UnknownType = b'U',
+    /// The packet failed a CRC or checksum verification
+    Checksum = b'C',
+    /// The notification could not be stored
+    NotificationStore = b'N',
+    /// The packet was malformed or could not be processed
+    Invalid = b'I',
+}
+
+/// Acknowledges an accepted packet, followed by its type byte
+pub const ACK: u8 = 0x06;
+/// Rejects a packet, followed by a `NackCode`
+pub const NACK: u8 = 0x15;
+
 const STX: u8 = 2;
 const ETX: u8 = 3;
 const PAYLOAD: u8 = 31; // Unit Separator
+const ESC: u8 = 27; // Escape, the following byte is treated as data
+
+/// The default time a partial packet may go without receiving a byte before it is dropped
+pub const PARTIAL_TIMEOUT_MS: u32 = 2000;
+/// The default fill level of the ring buffer, as a percentage of its capacity, at which it is nearly full
+pub const HIGH_WATERMARK_PERCENT: usize = 75;
+
+const RESPONSE: u8 = b'R'; // Type byte of outbound syscall responses
+
+const CRC_FLAG: u8 = b'C'; // The packet has a trailing CRC16
+const CRC_LEN: usize = 2; // CRC16 as raw big-endian bytes
+const CRC_POLY: u16 = 0x1021; // CRC16-CCITT
+const CRC_INIT: u16 = 0xFFFF;
+const METADATA_FLAG: u8 = b'M'; // The application name and version are sent before the checksum
+const CHUNK_FLAG: u8 = b'O'; // The application is a chunk, its offset is sent between the checksum and the image
+const LAST_CHUNK_FLAG: u8 = b'E'; // As `CHUNK_FLAG`, the last chunk of the application
+const TIMESTAMP_FLAG: u8 = b'T'; // The notification is sent with a unix timestamp section before the source
+const LENGTH_FLAG: u8 = b'L'; // The frame length follows as two big-endian bytes, see `IngressManager::match_rb`
+const LENGTH_LEN: usize = 2; // The frame length in bytes
+const RUN_LEN: usize = 32; // The most bytes copied at once by the fast path of `match_rb`
 
-pub struct IngressManager {
+/// The control bytes used to frame packets
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FramingConfig {
+    /// Start of packet
+    pub stx: u8,
+    /// End of packet
+    pub etx: u8,
+    /// Section separator
+    pub sep: u8,
+}
+
+impl Default for FramingConfig {
+    /// The standard framing, STX, ETX and the unit separator
+    fn default() -> Self {
+        FramingConfig {
+            stx: STX,
+            etx: ETX,
+            sep: PAYLOAD,
+        }
+    }
+}
+
+impl FramingConfig {
+    /// Escape `data` into `out` so that any control bytes within it are transmitted as data
+    ///
+    /// Returns the number of bytes written into `out`
+    pub fn escape_into(&self, data: &[u8], out: &mut [u8]) -> Result<usize, IngressError> {
+        let mut idx = 0;
+        for byte in data {
+            if self.is_control(*byte) {
+                *out.get_mut(idx).ok_or(IngressError::BufferFull)? = ESC;
+                idx += 1;
+            }
+            *out.get_mut(idx).ok_or(IngressError::BufferFull)? = *byte;
+            idx += 1;
+        }
+        Ok(idx)
+    }
+
+    /// Whether `byte` frames a packet or escapes the next byte
+    fn is_control(&self, byte: u8) -> bool {
+        byte == self.stx || byte == self.etx || byte == self.sep || byte == ESC
+    }
+}
+
+/// The type byte of the packets of `btype`
+fn type_byte(btype: Type) -> u8 {
+    match btype {
+        Type::Notification => b'N',
+        Type::Syscall => b'S',
+        Type::Application => b'A',
+        Type::NotificationDismiss => b'D',
+        Type::Unknown => b'?',
+    }
+}
+
+/// Update a CRC16-CCITT (polynomial 0x1021, not reflected and without a final xor, i.e CCITT-FALSE) with `bytes`,
+/// starting from `CRC_INIT`
+fn crc16_ccitt(mut crc: u16, bytes: &[u8]) -> u16 {
+    for byte in bytes {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ CRC_POLY } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// Decode a group of up to four base64 characters, returning the bytes and how many of them are valid.
+/// The group may be padded with `=`, or cut short at the end of the image
+fn decode_base64(group: &[u8]) -> Option<([u8; 3], usize)> {
+    let len = group.iter().position(|&c| c == b'=').unwrap_or(group.len());
+    if len < 2 || group[len..].iter().any(|&c| c != b'=') {
+        return None;
+    }
+    let mut bits = 0u32;
+    for (idx, &c) in group[..len].iter().enumerate() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits |= u32::from(value) << (18 - 6 * idx);
+    }
+    Some(([(bits >> 16) as u8, (bits >> 8) as u8, bits as u8], len - 1))
+}
+
+/// Escape `data` into `out` so that any framing bytes within it are transmitted as data,
+/// see `FramingConfig::escape_into` for non standard framing
+///
+/// Returns the number of bytes written into `out`
+pub fn escape_into(data: &[u8], out: &mut [u8]) -> Result<usize, IngressError> {
+    FramingConfig::default().escape_into(data, out)
+}
+
+/// The ingress manager, `N` being the capacity of the internal ring buffer
+pub struct IngressManager<N = U512>
+where
+    N: ArrayLength<u8>,
+{
     buffer: Buffer,
-    rb: Queue<u8, U512>,
+    rb: Queue<u8, N>,
+    /// the number of buffered bytes at which the ring buffer is nearly full
+    high_watermark: usize,
+    outbound: Queue<u8, U256>,
+    /// the index of the next notification to list, see `Syscall::ListNotifications`
+    listing: Option<usize>,
+    framing: FramingConfig,
     state: State,
 
     hex_chars: [u8; 2],
+    /// the base64 group being decoded, `hex_idx` being the number of characters in it
+    base64_chars: [u8; 4],
     hex_idx: usize,
+    hex_errors: u32,
+    error_budget: u32,
 
-    nsi: [usize; 3],
+    nsi: Vec<usize, U8>,
     nsi_idx: usize,
+
+    escaped: bool,
+    crc: bool,
+    require_crc: bool,
+    /// the CRC of the frame bytes received so far, excluding the last `CRC_LEN` which are held in `crc_tail`
+    frame_crc: u16,
+    /// the last `CRC_LEN` frame bytes and whether each was escaped, the CRC itself once the ETX arrives
+    crc_tail: [(bool, u8); CRC_LEN],
+    crc_tail_len: usize,
+    /// the number of notification bytes that did not fit in the buffer
+    overflowed: usize,
+    binary: bool,
+    /// the application image is base64 rather than hex
+    base64: bool,
+    /// the priority flag of the notification being parsed
+    priority: u8,
+    /// the slot flag of the application being parsed
+    slot: u8,
+    /// the notification being parsed has a timestamp section
+    timestamped: bool,
+    /// the timestamp of the notification being parsed, if it was sent and valid
+    timestamp: Option<u32>,
+    /// the bytes left in a frame sent with its length, before the ETX
+    remaining: Option<usize>,
+    /// the whole of the frame sent with its length has been buffered
+    length_checked: bool,
+    metadata: bool,
+    /// the chunk flags of the application being parsed, chunk and last chunk
+    chunk: Option<bool>,
+    /// the ram offset of the next byte of an application chunk
+    offset: usize,
+    /// the checksum staged before an application chunk, the slot is kept if the chunk has the same checksum
+    staged_checksum: Option<u32>,
+
+    now_ms: u32,
+    last_byte_ms: u32,
+    timeout_ms: u32,
+    dropped_partial: u32,
+    stats: IngressStats,
+    /// why the frame being parsed was dropped
+    aborted: Option<AbortReason>,
+    /// the notification being processed could not be stored
+    notification_failed: bool,
+    capture_unknown: bool,
+    unknown_frame: Option<Buffer>,
+    max_frame: Option<usize>,
+    /// the number of bytes received since the STX of the frame being parsed
+    frame_len: usize,
 }
 
-impl IngressManager {
+/// The ingress manager with the default ring buffer capacity
+pub type DefaultIngressManager = IngressManager<U512>;
+
+impl<N> IngressManager<N>
+where
+    N: ArrayLength<u8>,
+{
 
-    /// Constructs a new IngressManager
+    /// Constructs a new IngressManager, using the standard framing
     pub fn new() -> Self {
+        Self::with_framing(FramingConfig::default())
+    }
+
+    /// Constructs a new IngressManager, dropping a partial packet that goes `timeout_ms` without receiving a
+    /// byte rather than `PARTIAL_TIMEOUT_MS`, see `tick`
+    pub fn with_timeout(timeout_ms: u32) -> Self {
+        let mut imgr = Self::new();
+        imgr.timeout_ms = timeout_ms;
+        imgr
+    }
+
+    /// Constructs a new IngressManager, framing packets with the given control bytes
+    pub fn with_framing(framing: FramingConfig) -> Self {
+        let rb: Queue<u8, N> = Queue::new();
         IngressManager {
             buffer: Buffer::default(),
-            rb: Queue::new(),
-            state: State::Init,
+            high_watermark: rb.capacity() * HIGH_WATERMARK_PERCENT / 100,
+            rb,
+            outbound: Queue::new(),
+            listing: None,
+            framing,
+            state: State::Wait,
             hex_chars: [0u8; 2],
+            base64_chars: [0u8; 4],
             hex_idx: 0,
-            nsi: [0usize; 3], // notification section pointers
+            hex_errors: 0,
+            error_budget: 0,
+            nsi: Vec::new(), // notification section pointers
             nsi_idx: 0,
+            escaped: false,
+            crc: false,
+            require_crc: cfg!(feature = "packet-crc"),
+            frame_crc: CRC_INIT,
+            crc_tail: [(false, 0); CRC_LEN],
+            crc_tail_len: 0,
+            overflowed: 0,
+            priority: DEFAULT_PRIORITY,
+            slot: 0,
+            timestamped: false,
+            timestamp: None,
+            remaining: None,
+            length_checked: false,
+            metadata: false,
+            chunk: None,
+            offset: 0,
+            staged_checksum: None,
+            binary: false,
+            base64: false,
+            now_ms: 0,
+            last_byte_ms: 0,
+            timeout_ms: PARTIAL_TIMEOUT_MS,
+            dropped_partial: 0,
+            stats: IngressStats::default(),
+            aborted: None,
+            notification_failed: false,
+            capture_unknown: false,
+            unknown_frame: None,
+            max_frame: None,
+            frame_len: 0,
+        }
+    }
+
+    /// Sets the time a partial packet may go without receiving a byte before it is dropped
+    pub fn set_timeout(&mut self, timeout_ms: u32) {
+        self.timeout_ms = timeout_ms;
+    }
+
+    /// Sets the number of corrupt hex pairs tolerated in an application image before the transfer is aborted.
+    /// Corrupt pairs are stored as `0` so the rest of the image keeps its offsets. The default of `0` aborts
+    /// on the first corrupt pair.
+    pub fn set_error_budget(&mut self, budget: u32) {
+        self.error_budget = budget;
+    }
+
+    /// The number of corrupt hex pairs skipped in the current (or last) application transfer
+    pub fn hex_errors(&self) -> u32 {
+        self.hex_errors
+    }
+
+    /// Update the managers notion of time, dropping any partial packet that has timed out.
+    /// The timeout is checked here rather than in `process`, as time only moves on when this is called
+    pub fn tick(&mut self, now_ms: u32) {
+        self.now_ms = now_ms;
+        if self.state != State::Wait && now_ms.wrapping_sub(self.last_byte_ms) > self.timeout_ms {
+            warn!("Partial buffer timed out: {:?}", self.buffer);
+            self.abort();
+            self.dropped_partial += 1;
+        }
+    }
+
+    /// Abort any in progress parse and discard any unprocessed data, i.e after the link is lost
+    pub fn reset(&mut self) {
+        while self.rb.dequeue().is_some() {}
+        self.listing = None;
+        self.abort();
+    }
+
+    /// Drop the partial packet, returning the state machine to `Wait`
+    fn abort(&mut self) {
+        self.buffer.clear();
+        self.hex_idx = 0;
+        self.hex_errors = 0;
+        self.nsi_idx = 0;
+        self.nsi = Vec::new();
+        self.escaped = false;
+        self.crc = false;
+        self.frame_crc = CRC_INIT;
+        self.crc_tail_len = 0;
+        self.overflowed = 0;
+        self.binary = false;
+        self.base64 = false;
+        self.priority = DEFAULT_PRIORITY;
+        self.slot = 0;
+        self.timestamped = false;
+        self.timestamp = None;
+        self.remaining = None;
+        self.length_checked = false;
+        self.metadata = false;
+        self.chunk = None;
+        self.aborted = None;
+        self.state = State::Wait;
+    }
+
+    /// The current state of the parser, useful for diagnosing stuck transfers
+    pub fn state(&self) -> IngressState {
+        match self.state {
+            State::Wait => IngressState::Wait,
+            State::Init => IngressState::Init,
+            State::Flags => IngressState::Flags,
+            State::FrameLength => IngressState::FrameLength,
+            State::Payload => IngressState::Payload,
+            State::ApplicationChecksum => IngressState::ApplicationChecksum,
+            State::ApplicationOffset => IngressState::ApplicationOffset,
+            State::ApplicationStore => IngressState::ApplicationStore,
+            State::ApplicationName => IngressState::ApplicationName,
+            State::ApplicationVersion => IngressState::ApplicationVersion,
+            State::NotificationTimestamp => IngressState::NotificationTimestamp,
+            State::NotificationSource => IngressState::NotificationSource,
+            State::NotificationTitle => IngressState::NotificationTitle,
+            State::NotificationBody => IngressState::NotificationBody,
+            State::Capture => IngressState::Capture,
         }
     }
 
+    /// The number of partial packets dropped due to timing out
+    pub fn dropped_partial(&self) -> u32 {
+        self.dropped_partial
+    }
+
+    /// Sets the most bytes a frame may have between its STX and ETX, a longer frame is dropped as soon as it exceeds it.
+    /// Unlimited by default, pass `None` to disable.
+    pub fn set_max_frame(&mut self, max_frame: Option<usize>) {
+        self.max_frame = max_frame;
+    }
+
+    /// Require notifications and syscalls to carry a CRC, dropping those without one.
+    /// Enabled by default with the `packet-crc` feature
+    pub fn set_require_crc(&mut self, require: bool) {
+        self.require_crc = require;
+    }
+
+    /// Capture frames of unknown type, instead of dropping them, for debugging senders. See `last_unknown_frame`
+    pub fn set_capture_unknown(&mut self, capture: bool) {
+        self.capture_unknown = capture;
+    }
+
+    /// The last frame of unknown type captured, from the type byte up to the ETX (exclusive),
+    /// truncated to the buffer capacity
+    pub fn last_unknown_frame(&self) -> Option<&[u8]> {
+        self.unknown_frame.as_ref().map(|frame| &frame.payload[..frame.len()])
+    }
+
+    /// The failure counters accumulated since construction or the last `reset_stats`
+    pub fn stats(&self) -> IngressStats {
+        self.stats
+    }
+
+    /// Zero the failure counters
+    pub fn reset_stats(&mut self) {
+        self.stats = IngressStats::default();
+    }
+
+    /// The number of bytes that can be written before the ring buffer is full
+    pub fn free_space(&self) -> usize {
+        self.rb.capacity() - self.rb.len()
+    }
+
+    /// Whether the ring buffer has filled to the high watermark, the transport should pause until it drains
+    pub fn is_nearly_full(&self) -> bool {
+        self.rb.len() >= self.high_watermark
+    }
+
+    /// Sets the number of buffered bytes at which `is_nearly_full` is true, defaulting to `HIGH_WATERMARK_PERCENT`
+    /// of the capacity
+    pub fn set_high_watermark(&mut self, bytes: usize) {
+        self.high_watermark = bytes;
+    }
+
     /// Write data into the internal ring buffer
     /// raw bytes being the core type allows the ingress manager to 
     /// be abstracted over the communication medium,
     /// in theory if we setup usb serial, we could have two ingress managers
     /// working in harmony 
-    pub fn write(&mut self, data: &[u8]) {
-        for byte in data {
-            match self.rb.enqueue(*byte) {
-                Ok(_) => {},
-                Err(e) => panic!("Ring buffer overflow by {:?} bytes", e)
+    ///
+    /// Returns the number of bytes accepted, which may be less than `data.len()` if the
+    /// ring buffer filled up part way through. If no bytes could be accepted `IngressError::BufferFull`
+    /// is returned, allowing the caller to apply backpressure.
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, IngressError> {
+        if !data.is_empty() && self.remaining.is_some() && !self.length_checked {
+            // a frame waiting to be buffered in full has not timed out, even though none of it is parsed yet
+            self.last_byte_ms = self.now_ms;
+        }
+        for (idx, byte) in data.iter().enumerate() {
+            if self.rb.enqueue(*byte).is_err() {
+                warn!("Ring buffer overflow by {} bytes", data.len() - idx);
+                self.stats.overflow_drops += 1;
+                if idx == 0 {
+                    return Err(IngressError::BufferFull);
+                }
+                return Ok(idx);
             }
         }
+        Ok(data.len())
     }
 
     /// Processs the internal ringbuffer's bytes and execute if the payload is complete
-    pub fn process(&mut self, system: &mut System) {
-        match self.match_rb(system) {
-            Some(buffer_type) => {
+    ///
+    /// Returns the type of the payload that was completed, if any
+    pub fn process(&mut self, system: &mut System) -> Result<Option<Type>, IngressError> {
+        self.process_packet(system).0
+    }
+
+    /// Like `process`, returning the `ACK` or `NACK` to send back to the sender when a packet was ended instead
+    /// of the result, which is logged. See `queue_response` to send it with the other outbound data
+    pub fn process_with_ack(&mut self, system: &mut System) -> Option<Response> {
+        let (result, response) = self.process_packet(system);
+        if let Err(err) = result {
+            error!("Failed to process ingress data {:?}", err);
+        }
+        response
+    }
+
+    /// Process the next packet, returning both the result and the response to send back to the sender
+    fn process_packet(&mut self, system: &mut System) -> (Result<Option<Type>, IngressError>, Option<Response>) {
+        self.list_notifications(system);
+        self.notification_failed = false;
+        let matched = self.match_rb(system);
+        let result = self.dispatch(matched, system);
+        let code = match (matched, result) {
+            (MatchResult::Incomplete, _) | (_, Ok(None)) => return (result, None),
+            (MatchResult::Aborted(AbortReason::UnknownType), _) | (MatchResult::Completed(Type::Unknown), _) => NackCode::UnknownType,
+            (MatchResult::Aborted(AbortReason::CrcMismatch), _)
+            | (MatchResult::Aborted(AbortReason::CrcMissing), _)
+            | (_, Err(IngressError::ChecksumMismatch)) => NackCode::Checksum,
+            (MatchResult::Aborted(_), _) | (_, Err(_)) => NackCode::Invalid,
+            (MatchResult::Completed(_), _) if self.notification_failed => NackCode::NotificationStore,
+            (MatchResult::Completed(btype), _) => {
+                return (result, Some([self.framing.stx, ACK, type_byte(btype), self.framing.etx]));
+            }
+        };
+        warn!("Rejecting packet {:?}", code);
+        (result, Some([self.framing.stx, NACK, code as u8, self.framing.etx]))
+    }
+
+    /// Execute the frame `match_rb` completed, if any
+    fn dispatch(&mut self, matched: MatchResult, system: &mut System) -> Result<Option<Type>, IngressError> {
+        match matched {
+            MatchResult::Aborted(AbortReason::InvalidUtf8) => Err(IngressError::InvalidUtf8),
+            MatchResult::Aborted(AbortReason::TooLong) => Err(IngressError::FrameTooLong),
+            MatchResult::Aborted(AbortReason::InvalidImage) => Err(IngressError::InvalidImage),
+            MatchResult::Aborted(AbortReason::Application(err)) => Err(IngressError::InvalidApplication(err)),
+            MatchResult::Aborted(reason) => {
+                warn!("Dropped frame {:?}", reason);
+                Ok(Some(Type::Unknown))
+            }
+            MatchResult::Completed(buffer_type) => {
                 match buffer_type {
-                    Type::Unknown => self.state = State::Wait, // if the type cannot be determined abort, and wait until next STX
+                    Type::Unknown => {
+                        // captured for debugging, see `last_unknown_frame`
+                    }
+                    Type::Application if self.chunk == Some(false) => {
+                        // more chunks to come
+                        info!("Application chunk stored, {} bytes", system.am().status().ram_used);
+                        return Ok(None);
+                    }
                     Type::Application => {
-                        match system.am().verify() {
-                            Ok(_) => {}
-                            Err(e) => panic!("{:?} || AMNG: {:?}", e, system.am().status()),
+                        if let Err(e) = system.am().verify() {
+                            error!("{:?} || AMNG: {:?}", e, system.am().status());
+                            self.state = State::Wait;
+                            if let AmError::ChecksumMismatch { .. } = e {
+                                self.stats.checksum_errors += 1;
+                                // never leave a corrupt image staged
+                                if let Err(err) = system.am().kill_slot(usize::from(self.slot)) {
+                                    error!("Failed to unload corrupt application {:?}", err);
+                                }
+                                return Err(IngressError::ChecksumMismatch);
+                            }
+                            return Err(IngressError::InvalidApplication(e));
                         }
                     }
                     Type::Notification => {
-                        self.nsi[2] = self.nsi_idx;
-                        info!("Adding notification from: {:?}, with section indexes {:?}", self.buffer, self.nsi);
-                        system.nm().add(&self.buffer, &self.nsi).unwrap_or_else(|err|{
-                            error!("Failed to add notification {:?}", err);
-                        });
+                        if !self.section_is_utf8() {
+                            error!("Dropping notification with invalid UTF-8 {:?}", self.buffer);
+                            return Err(IngressError::InvalidUtf8);
+                        }
+                        if self.nsi.push(self.nsi_idx).is_err() {
+                            error!("Too many sections in notification {:?}", self.buffer);
+                            self.notification_failed = true;
+                        } else {
+                            info!("Adding notification from: {:?}, with section indexes {:?}", self.buffer, self.nsi);
+                            let timestamp = match self.timestamp {
+                                Some(timestamp) => timestamp,
+                                None => system.timestamp(),
+                            };
+                            match system.nm().add(&self.buffer, &self.nsi, self.priority, timestamp) {
+                                Ok(added) => {
+                                    if let Some(evicted) = added.evicted {
+                                        warn!("Evicted notification from {}", evicted.source());
+                                    }
+                                    // let the sender know the id to dismiss it with
+                                    self.respond(SyscallResponse::Added(added.id));
+                                    system.haptic().vibrate(NOTIFICATION_BUZZ_MS, Pattern::Single);
+                                },
+                                Err(NotificationError::Muted) => info!("Dropped notification from a muted source"),
+                                Err(err) => {
+                                    error!("Failed to add notification {:?}", err);
+                                    self.notification_failed = true;
+                                }
+                            }
+                        }
                     },
+                    Type::NotificationDismiss => {
+                        let id = match self.buffer.as_str() {
+                            Ok(id) => id.parse::<u32>(),
+                            Err(_) => {
+                                error!("Dropping dismissal with invalid UTF-8 {:?}", self.buffer);
+                                return Err(IngressError::InvalidUtf8);
+                            }
+                        };
+                        match id {
+                            Ok(id) => match system.nm().remove(id) {
+                                Ok(()) => info!("Dismissed notification {}", id),
+                                Err(err) => warn!("Failed to dismiss notification {}: {:?}", id, err),
+                            },
+                            Err(_) => warn!("Invalid notification id {:?}", self.buffer),
+                        }
+                    }
                     Type::Syscall => {
                         info!("Parsing syscall from: {:?}", self.buffer);
-                        match Syscall::from_str(self.buffer.as_str()) {
-                            Ok(syscall) => syscall.execute(system),
-                            Err(e) => error!("Failed to parse syscall {:?}", e)
-                        }
+                        let payload = match self.buffer.as_str() {
+                            Ok(payload) => payload,
+                            Err(_) => {
+                                error!("Dropping syscall with invalid UTF-8 {:?}", self.buffer);
+                                return Err(IngressError::InvalidUtf8);
+                            }
+                        };
+                        let response = match Syscall::from_str(payload) {
+                            Ok(syscall) => {
+                                if syscall == Syscall::ListNotifications {
+                                    self.listing = Some(0);
+                                }
+                                syscall.execute(system)
+                            },
+                            Err(e) => {
+                                error!("Failed to parse syscall {:?}", e);
+                                SyscallResponse::Error(e)
+                            }
+                        };
+                        self.respond(response);
+                        self.list_notifications(system);
                     }
                 }
+                Ok(Some(buffer_type))
             },
-            None => {}
+            MatchResult::Incomplete => Ok(None)
+        }
+    }
+
+    /// Read pending outbound data into `data`, returning the number of bytes read
+    pub fn read(&mut self, data: &mut [u8]) -> usize {
+        let mut idx = 0;
+        while idx < data.len() {
+            match self.outbound.dequeue() {
+                Some(byte) => data[idx] = byte,
+                None => break,
+            }
+            idx += 1;
+        }
+        idx
+    }
+
+    /// Queue an `ACK` or `NACK` from `process_with_ack` after any pending outbound data, see `read` and `flush`.
+    /// Returns `IngressError::BufferFull` without queueing any of it if the outbound queue does not have room
+    pub fn queue_response(&mut self, response: &Response) -> Result<(), IngressError> {
+        if self.outbound.capacity() - self.outbound.len() < response.len() {
+            return Err(IngressError::BufferFull);
+        }
+        for byte in response.iter() {
+            self.outbound.enqueue(*byte).ok();
+        }
+        Ok(())
+    }
+
+    /// Write as much pending outbound data into `tx` as it will accept without blocking
+    pub fn flush<W>(&mut self, tx: &mut W)
+    where
+        W: embedded_hal::serial::Write<u8>,
+    {
+        while let Some(byte) = self.outbound.iter().next().cloned() {
+            if tx.write(byte).is_err() {
+                break;
+            }
+            self.outbound.dequeue();
+        }
+    }
+
+    /// Frame a syscall response into the outbound queue, the response is dropped if it does not fit
+    fn respond(&mut self, response: SyscallResponse) {
+        if let Err(response) = self.try_respond(response) {
+            warn!("Outbound queue full, dropping syscall response {:?}", response);
+        }
+    }
+
+    /// Frame a syscall response into the outbound queue, returning the response if there is not enough room
+    fn try_respond<'a>(&mut self, response: SyscallResponse<'a>) -> Result<(), SyscallResponse<'a>> {
+        use core::fmt::Write;
+        let mut payload: heapless::String<U96> = heapless::String::new();
+        if write!(payload, "{}", response).is_err() {
+            error!("Syscall response too large {:?}", response);
+            return Ok(());
+        }
+        let mut escaped = [0u8; 192];
+        let len = match self.framing.escape_into(payload.as_bytes(), &mut escaped) {
+            Ok(len) => len,
+            Err(err) => {
+                error!("Failed to escape syscall response {:?}", err);
+                return Ok(());
+            }
+        };
+        // STX, type, PAYLOAD ... ETX
+        if self.outbound.capacity() - self.outbound.len() < len + 4 {
+            return Err(response);
+        }
+        let framing = self.framing;
+        for byte in [framing.stx, RESPONSE, framing.sep].iter().chain(escaped[..len].iter()).chain([framing.etx].iter()) {
+            self.outbound.enqueue(*byte).ok();
+        }
+        Ok(())
+    }
+
+    /// Frame as many of the notifications being listed as fit in the outbound queue, the rest are
+    /// framed once the queue has been flushed
+    fn list_notifications(&mut self, system: &mut System) {
+        while let Some(index) = self.listing {
+            if index >= system.nm().idx() {
+                self.listing = None;
+                return;
+            }
+            let mut queued = false;
+            system.nm().peek_notification(index, |notification| {
+                queued = self.try_respond(SyscallResponse::Notification {
+                    index,
+                    id: notification.id(),
+                    received_ms: notification.received_ms(),
+                    source: notification.source(),
+                    title: notification.title(),
+                }).is_ok();
+            });
+            if !queued {
+                return; // wait for the outbound queue to drain
+            }
+            self.listing = Some(index + 1);
         }
     }
 
@@ -116,12 +853,88 @@ impl IngressManager {
                 self.buffer.btype = self.determine_type(byte);
                 info!("New buffer of type {:?}", self.buffer.btype);
                 if let Type::Unknown = self.buffer.btype {
-                    error!("Buffer type is unknown. Going back to wait state.");
-                    self.state = State::Wait 
+                    self.stats.unknown_type += 1;
+                    if self.capture_unknown {
+                        warn!("Buffer type {} is unknown, capturing", byte);
+                        let _ = self.buffer.write(byte);
+                        self.state = State::Capture;
+                    } else {
+                        error!("Buffer type is unknown. Going back to wait state.");
+                        self.drop_frame(AbortReason::UnknownType);
+                    }
+                } else {
+                    self.state = State::Flags;
+                }
+            }
+            State::Flags => {
+                match (byte, self.buffer.btype) {
+                    (CRC_FLAG, Type::Notification) | (CRC_FLAG, Type::Syscall) | (CRC_FLAG, Type::NotificationDismiss) => self.crc = true,
+                    (b'0'..=b'9', Type::Notification) => self.priority = byte - b'0',
+                    (b'0'..=b'9', Type::Application) => self.slot = byte - b'0',
+                    (METADATA_FLAG, Type::Application) => self.metadata = true,
+                    (TIMESTAMP_FLAG, Type::Notification) => self.timestamped = true,
+                    (LENGTH_FLAG, _) if self.remaining.is_none() => self.state = State::FrameLength,
+                    (CHUNK_FLAG, Type::Application) => self.chunk = Some(false),
+                    (LAST_CHUNK_FLAG, Type::Application) => self.chunk = Some(true),
+                    _ => {
+                        error!("Invalid flag {} for buffer of type {:?}", byte as char, self.buffer.btype);
+                        self.drop_frame(AbortReason::Invalid);
+                    }
                 }
             }
             State::Payload => {
-                self.buffer.write(byte);
+                self.buffer.write(byte).unwrap_or_else(|err| {
+                    error!("Dropping oversized payload {:?}", err);
+                    self.drop_frame(AbortReason::Invalid);
+                });
+            }
+            State::NotificationTimestamp => {
+                // validated once the section ends, an invalid timestamp is replaced by the time of arrival
+                let _ = self.buffer.write(byte);
+            }
+            State::FrameLength => {
+                let _ = self.buffer.write(byte);
+                if self.buffer.len() == LENGTH_LEN {
+                    let len = u16::from_be_bytes([self.buffer.payload[0], self.buffer.payload[1]]);
+                    self.remaining = Some(usize::from(len));
+                    self.buffer.clear();
+                    self.state = State::Flags;
+                }
+            }
+            State::ApplicationOffset => {
+                if !byte.is_ascii_digit() || self.buffer.write(byte).is_err() {
+                    error!("Invalid application chunk offset");
+                    self.drop_frame(AbortReason::Invalid);
+                }
+            }
+            State::ApplicationName | State::ApplicationVersion => {
+                let result = if self.state == State::ApplicationName {
+                    system.am().write_name_byte(byte)
+                } else {
+                    system.am().write_version_byte(byte)
+                };
+                result.unwrap_or_else(|err| {
+                    error!("Invalid application metadata {:?}", err);
+                    self.drop_frame(AbortReason::Invalid);
+                });
+            }
+            State::ApplicationChecksum | State::ApplicationStore if self.binary => {
+                let result = if self.state == State::ApplicationChecksum {
+                    system.am().write_checksum_byte(byte)
+                } else {
+                    self.store(system, &[byte])
+                };
+                result.unwrap_or_else(|err|{
+                    error!("Failed to write application byte {:?}", err);
+                    self.drop_frame(AbortReason::Invalid);
+                });
+            }
+            State::ApplicationStore if self.base64 => {
+                self.base64_chars[self.hex_idx] = byte;
+                self.hex_idx += 1;
+                if self.hex_idx == self.base64_chars.len() {
+                    self.store_base64(system);
+                }
             }
             State::ApplicationChecksum | State::ApplicationStore => {
                 self.hex_chars[self.hex_idx] = byte;
@@ -133,27 +946,35 @@ impl IngressManager {
                                 Ok(byte) => {
                                     system.am().write_checksum_byte(byte).unwrap_or_else(|err|{
                                         error!("Failed to write checksum byte {:?}", err);
-                                        self.state = State::Wait;
+                                        self.drop_frame(AbortReason::Invalid);
                                     });
                                 }
                                 Err(err) => {
                                     error!("Failed to parse hex bytes to byte {:?}", err);
-                                    self.state = State::Wait; // abort
+                                    self.drop_frame(AbortReason::InvalidImage);
                                 }
                             }
                         }
                         State::ApplicationStore => {
-                            match hex_byte_to_byte(self.hex_chars[0], self.hex_chars[1]) {
-                                Ok(byte) => {
-                                    system.am().write_ram_byte(byte).unwrap_or_else(|err|{
-                                        error!("Failed to write ram byte {:?}", err);
-                                        self.state = State::Wait;
-                                    });
+                            let byte = match hex_byte_to_byte(self.hex_chars[0], self.hex_chars[1]) {
+                                Ok(byte) => Some(byte),
+                                Err(err) if self.hex_errors < self.error_budget => {
+                                    // skip the pair, padding to preserve the offset of the rest of the image
+                                    warn!("Skipping corrupt hex pair {:?}", err);
+                                    self.hex_errors += 1;
+                                    Some(0)
                                 }
                                 Err(err) => {
                                     error!("Failed to parse hex bytes to byte {:?}", err);
-                                    self.state = State::Wait; // abort
+                                    self.drop_frame(AbortReason::InvalidImage);
+                                    None
                                 }
+                            };
+                            if let Some(byte) = byte {
+                                self.store(system, &[byte]).unwrap_or_else(|err|{
+                                    error!("Failed to write ram byte {:?}", err);
+                                    self.drop_frame(AbortReason::Application(err));
+                                });
                             }
                         }
                         _ => unreachable!()
@@ -162,8 +983,16 @@ impl IngressManager {
                 }
             }
             State::NotificationBody | State::NotificationTitle | State::NotificationSource => {
-                self.nsi_idx += 1;
-                self.buffer.write(byte);
+                // once full, keep consuming until ETX so the notification is clipped rather than lost
+                if self.buffer.write(byte).is_ok() {
+                    self.nsi_idx += 1;
+                } else {
+                    self.overflowed += 1;
+                }
+            }
+            State::Capture => {
+                // keep what fits, the rest of the frame is dropped
+                let _ = self.buffer.write(byte);
             }
             State::Wait => {
                 // do nothing, useless bytes
@@ -172,54 +1001,235 @@ impl IngressManager {
     }
 
     /// Run the internal state machine to parse payloads over a byte stream in the ring buffer
-    fn match_rb(&mut self, system: &mut System) -> Option<Type> {
+    fn match_rb(&mut self, system: &mut System) -> MatchResult {
+        let framing = self.framing;
         if !self.rb.is_empty() {
-            while let Some(byte) = self.rb.dequeue() {
+            loop {
+                if let Some(remaining) = self.remaining {
+                    if !self.length_checked {
+                        if self.rb.len() < remaining.min(self.rb.capacity()) {
+                            // wait until the whole frame has been buffered, or as much of it as fits
+                            return MatchResult::Incomplete;
+                        }
+                        self.length_checked = true;
+                    }
+                }
+                if let Some(len) = self.copy_run(system) {
+                    if self.frame_too_long(len) {
+                        return MatchResult::Aborted(AbortReason::TooLong);
+                    }
+                    continue;
+                }
+                let byte = match self.remaining {
+                    Some(0) => match self.rb.iter().next() {
+                        Some(&byte) if byte == framing.etx && !self.escaped => {
+                            self.rb.dequeue();
+                            byte
+                        }
+                        Some(_) => {
+                            // the frame is over, even if its ETX was lost
+                            warn!("Missing ETX after a frame of known length");
+                            self.escaped = false;
+                            framing.etx
+                        }
+                        None => break,
+                    },
+                    _ => match self.rb.dequeue() {
+                        Some(byte) => byte,
+                        None => break,
+                    },
+                };
+                if self.escaped || byte != framing.etx {
+                    if let Some(remaining) = self.remaining.as_mut() {
+                        *remaining -= 1;
+                    }
+                }
+                self.last_byte_ms = self.now_ms;
+                if self.escaped || (byte != framing.stx && byte != framing.etx) {
+                    self.update_crc(&[byte]);
+                }
+                if (self.escaped || byte != framing.etx) && self.frame_too_long(1) {
+                    return MatchResult::Aborted(AbortReason::TooLong);
+                }
+                if self.escaped {
+                    /* Escaped byte, never treated as a control byte */
+                    self.escaped = false;
+                    self.run_state_machine(byte, system);
+                    continue;
+                }
                 match byte {
-                    STX => {
+                    ESC => {
+                        self.escaped = true;
+                    }
+                    b if b == framing.stx => {
                         if self.state != State::Wait {
                             warn!("Partial buffer detected: {:?}", self.buffer);
+                            self.stats.partial_resets += 1;
                         }
                         /* Start of packet */
+                        self.frame_len = 0;
                         self.hex_idx = 0;
+                        self.hex_errors = 0;
                         self.nsi_idx = 0;
+                        self.nsi = Vec::new();
+                        self.crc = false;
+                        self.frame_crc = CRC_INIT;
+                        self.crc_tail_len = 0;
+                        self.overflowed = 0;
+                        self.binary = false;
+                        self.base64 = false;
+                        self.priority = DEFAULT_PRIORITY;
+                        self.slot = 0;
+                        self.timestamped = false;
+                        self.timestamp = None;
+                        self.remaining = None;
+                        self.length_checked = false;
+                        self.metadata = false;
+                        self.chunk = None;
+                        self.aborted = None;
                         self.buffer.clear();
                         self.state = State::Init; // activate processing
                     }
-                    ETX => {
+                    b if b == framing.etx => {
                         /* End of packet */
                         /* Finalize messge then reset state machine ready for next msg*/
+                        if self.remaining.take().filter(|&remaining| remaining > 0).is_some() {
+                            warn!("Frame ended before its length");
+                            self.state = State::Wait;
+                            self.aborted = None;
+                            return MatchResult::Aborted(AbortReason::Invalid);
+                        }
+                        if self.state == State::ApplicationStore && self.base64 && self.hex_idx > 0 {
+                            // the final group was sent without padding
+                            self.store_base64(system);
+                        }
+                        let state = self.state;
                         self.state = State::Wait;
-                        return Some(self.buffer.btype);
+                        if let Some(reason) = self.aborted.take() {
+                            return MatchResult::Aborted(reason);
+                        }
+                        match state {
+                            State::Wait => warn!("Ignoring ETX outside of a frame"),
+                            State::Flags | State::NotificationTimestamp if self.buffer.btype == Type::Notification => {
+                                warn!("Dropping notification without any sections");
+                                return MatchResult::Aborted(AbortReason::Invalid);
+                            }
+                            State::Capture => {
+                                self.unknown_frame = Some(self.buffer);
+                                return MatchResult::Completed(Type::Unknown);
+                            }
+                            _ if self.crc && !self.verify_crc() => {
+                                self.stats.checksum_errors += 1;
+                                return MatchResult::Aborted(AbortReason::CrcMismatch);
+                            }
+                            _ if !self.crc && self.require_crc && self.buffer.btype != Type::Application => {
+                                warn!("Dropping {:?} without a CRC", self.buffer.btype);
+                                self.stats.checksum_errors += 1;
+                                return MatchResult::Aborted(AbortReason::CrcMissing);
+                            }
+                            _ => {
+                                self.stats.packets_ok += 1;
+                                return MatchResult::Completed(self.buffer.btype);
+                            }
+                        }
+                    }
+                    b if b == framing.sep && self.state == State::FrameLength => {
+                        error!("Unescaped separator in the frame length");
+                        self.drop_frame(AbortReason::Invalid);
                     }
-                    PAYLOAD => {
+                    b if b == framing.sep => {
                         match self.buffer.btype {
+                            Type::Unknown if self.state == State::Capture => {
+                                self.run_state_machine(byte, system);
+                            }
                             Type::Unknown => {
-                                warn!("Dropping buffer of unknown type {:?}", self.buffer.btype);
-                                self.state = State::Wait
+                                // already dropped, ignore the rest of the frame
                             }
                             Type::Application => {
-                                if self.state == State::ApplicationChecksum {
-                                    // We've parsed the checksum, now we write the data into ram
-                                    self.state = State::ApplicationStore
-                                } else {
-                                    // reset before we load the new application
-                                    system.am().kill().unwrap_or_else(|err| {
-                                        warn!("Failed to kill application, writing over live data! {:?}", err);
-                                    });
-                                    // parse the checksum
+                                if self.state == State::ApplicationName {
+                                    self.state = State::ApplicationVersion;
+                                } else if self.state == State::ApplicationVersion {
                                     self.state = State::ApplicationChecksum;
+                                } else if self.state == State::ApplicationChecksum {
+                                    let written = system.am().checksum_written();
+                                    if written != CHECKSUM_LEN {
+                                        error!("Invalid application checksum length {}, expected {}", written, CHECKSUM_LEN);
+                                        self.drop_frame(AbortReason::Invalid);
+                                    } else if self.chunk.is_some() {
+                                        self.resume_chunk(system);
+                                    } else {
+                                        // We've parsed the checksum, now we write the data into ram
+                                        self.state = State::ApplicationStore
+                                    }
+                                } else if self.state == State::ApplicationOffset {
+                                    match self.buffer.as_str().ok().and_then(|offset| offset.parse().ok()) {
+                                        Some(offset) => {
+                                            self.offset = offset;
+                                            self.state = State::ApplicationStore
+                                        }
+                                        None => {
+                                            error!("Invalid application chunk offset");
+                                            self.drop_frame(AbortReason::Invalid);
+                                        }
+                                    }
+                                } else if self.chunk.is_some() {
+                                    // keep the slot, it is reset once the checksum shows this is a new application
+                                    let slot = usize::from(self.slot);
+                                    let cleared = system.am().select_slot(slot).and_then(|_| {
+                                        self.staged_checksum = system.am().staged_checksum();
+                                        system.am().clear_checksum()
+                                    });
+                                    match cleared {
+                                        Ok(_) if self.metadata => {
+                                            error!("Application chunks cannot carry metadata");
+                                            self.drop_frame(AbortReason::Invalid);
+                                        }
+                                        Ok(_) => self.state = State::ApplicationChecksum,
+                                        Err(err) => {
+                                            error!("Failed to load application into slot {} {:?}", slot, err);
+                                            self.drop_frame(AbortReason::Invalid);
+                                        }
+                                    }
+                                } else {
+                                    // reset the slot before we load the new application
+                                    let slot = usize::from(self.slot);
+                                    match system.am().select_slot(slot).and_then(|_| system.am().kill_slot(slot)) {
+                                        Ok(_) if self.metadata => self.state = State::ApplicationName,
+                                        Ok(_) => self.state = State::ApplicationChecksum, // parse the checksum
+                                        Err(err) => {
+                                            error!("Failed to load application into slot {} {:?}", slot, err);
+                                            self.drop_frame(AbortReason::Invalid);
+                                        }
+                                    }
                                 }
                             }
                             Type::Notification => {
-                                if self.state == State::NotificationSource { // we've parsed the app source
-                                    self.nsi[0] = self.nsi_idx;
-                                    self.state = State::NotificationTitle;
-                                } else if self.state == State::NotificationTitle { // weve parsed the title
-                                    self.nsi[1] = self.nsi_idx;
-                                    self.state = State::NotificationBody;
-                                } else {
-                                    self.state = State::NotificationSource; // new parse
+                                match self.state {
+                                    State::NotificationSource | State::NotificationTitle | State::NotificationBody => {
+                                        // we've parsed a section, record where it ends, leaving room for the final section
+                                        if !self.section_is_utf8() {
+                                            warn!("Dropping notification with invalid UTF-8 {:?}", self.buffer);
+                                            self.drop_frame(AbortReason::InvalidUtf8);
+                                        } else if self.nsi.push(self.nsi_idx).is_err() || self.nsi.len() == self.nsi.capacity() {
+                                            warn!("Dropping notification with too many sections {:?}", self.buffer);
+                                            self.drop_frame(AbortReason::Invalid);
+                                        } else if self.state == State::NotificationSource {
+                                            self.state = State::NotificationTitle;
+                                        } else {
+                                            // any sections after the title are part of the body
+                                            self.state = State::NotificationBody;
+                                        }
+                                    }
+                                    State::NotificationTimestamp => {
+                                        self.timestamp = self.buffer.as_str().ok().and_then(|timestamp| timestamp.parse().ok());
+                                        if self.timestamp.is_none() {
+                                            warn!("Invalid notification timestamp {:?}", self.buffer);
+                                        }
+                                        self.buffer.clear();
+                                        self.state = State::NotificationSource;
+                                    }
+                                    _ if self.timestamped => self.state = State::NotificationTimestamp,
+                                    _ => self.state = State::NotificationSource, // new parse
                                 }
                             }
                             _ => self.state = State::Payload,
@@ -232,42 +1242,1569 @@ impl IngressManager {
                 }
             }
         }
-        None
+        MatchResult::Incomplete
     }
 
-    /// Based on the type byte, determine the type of the incoming payload
-    fn determine_type(&mut self, type_byte: u8) -> Type {
-        self.buffer.btype = match type_byte {
-            b'N' => Type::Notification, /* NOTIFICATION i.e FB Msg */
-            b'S' => Type::Syscall,
-            b'A' => Type::Application,  /* Load Application */
-            _ => Type::Unknown,
+    /// Count `len` more bytes of the frame being parsed, dropping it if it exceeds the maximum frame size
+    fn frame_too_long(&mut self, len: usize) -> bool {
+        if self.state == State::Wait {
+            return false;
+        }
+        self.frame_len += len;
+        match self.max_frame {
+            Some(max_frame) if self.frame_len > max_frame => {
+                warn!("Dropping frame longer than {} bytes", max_frame);
+                self.stats.frames_too_long += 1;
+                self.drop_frame(AbortReason::TooLong);
+                // reported now rather than at the ETX
+                self.aborted = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Drop the payload of the frame being parsed, ignoring the rest of the frame
+    fn drop_frame(&mut self, reason: AbortReason) {
+        self.buffer.btype = Type::Unknown;
+        self.aborted = Some(reason);
+        self.state = State::Wait;
+    }
+
+    /// The fast path of `match_rb` for syscall payloads and binary applications, copying a run of up to `RUN_LEN`
+    /// non control bytes in one go, with the same result as passing each byte through the state machine.
+    ///
+    /// Returns the length of the run, `None` if there was no run to copy
+    fn copy_run(&mut self, system: &mut System) -> Option<usize> {
+        let fast = match self.state {
+            State::Payload => true,
+            State::ApplicationStore => self.binary,
+            _ => false,
         };
-        self.buffer.btype
+        if !fast || self.escaped {
+            return None;
+        }
+        let mut run = [0u8; RUN_LEN];
+        let mut len = 0;
+        let limit = self.remaining.map_or(RUN_LEN, |remaining| remaining.min(RUN_LEN));
+        while len < limit {
+            match self.rb.iter().next() {
+                Some(&byte) if !self.framing.is_control(byte) => {
+                    run[len] = byte;
+                    len += 1;
+                    self.rb.dequeue();
+                }
+                _ => break,
+            }
+        }
+        if len == 0 {
+            return None;
+        }
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= len;
+        }
+        self.last_byte_ms = self.now_ms;
+        self.update_crc(&run[..len]);
+        let failed = if self.state == State::Payload {
+            self.buffer.write_slice(&run[..len]).map_err(|err| {
+                error!("Dropping oversized payload {:?}", err);
+            }).is_err()
+        } else {
+            self.store(system, &run[..len]).map_err(|err| {
+                error!("Failed to write application byte {:?}", err);
+            }).is_err()
+        };
+        if failed {
+            self.drop_frame(AbortReason::Invalid);
+        }
+        Some(len)
     }
 
-}
+    /// Write application bytes into ram, at the chunk offset if the application is sent in chunks
+    fn store(&mut self, system: &mut System, bytes: &[u8]) -> Result<(), AmError> {
+        if self.chunk.is_none() {
+            return system.am().write_ram(bytes);
+        }
+        let offset = self.offset;
+        self.offset += bytes.len();
+        system.am().write_ram_at(offset, bytes)
+    }
+
+    /// Decode the base64 group collected so far into the application ram
+    fn store_base64(&mut self, system: &mut System) {
+        let group = self.base64_chars;
+        let len = self.hex_idx;
+        self.hex_idx = 0;
+        match decode_base64(&group[..len]) {
+            Some((bytes, count)) => {
+                self.store(system, &bytes[..count]).unwrap_or_else(|err| {
+                    error!("Failed to write ram bytes {:?}", err);
+                    self.drop_frame(AbortReason::Application(err));
+                });
+            }
+            None => {
+                error!("Invalid base64 group {:?}", &group[..len]);
+                self.drop_frame(AbortReason::InvalidImage);
+            }
+        }
+    }
+
+    /// Once the checksum of an application chunk has been parsed, reset the slot unless the chunk belongs to the
+    /// application already being loaded, then parse the offset
+    fn resume_chunk(&mut self, system: &mut System) {
+        let staged = self.staged_checksum;
+        if let Some(checksum) = system.am().staged_checksum().filter(|checksum| Some(*checksum) != staged) {
+            info!("New chunked application, resetting slot {}", self.slot);
+            let slot = usize::from(self.slot);
+            let rewritten = system.am().kill_slot(slot).and_then(|_| {
+                checksum.to_be_bytes().iter().try_for_each(|byte| system.am().write_checksum_byte(*byte))
+            });
+            if let Err(err) = rewritten {
+                error!("Failed to reset slot {} {:?}", slot, err);
+                self.drop_frame(AbortReason::Invalid);
+                return;
+            }
+        }
+        self.buffer.clear();
+        self.state = State::ApplicationOffset;
+    }
 
+    /// Validate the section of the notification ending at `nsi_idx`. If the buffer was truncated part way through a
+    /// character, the incomplete character is trimmed rather than invalidating the notification.
+    fn section_is_utf8(&mut self) -> bool {
+        let start = self.nsi.last().cloned().unwrap_or(0);
+        match core::str::from_utf8(&self.buffer.payload[start..self.nsi_idx]) {
+            Ok(_) => true,
+            Err(err) if err.error_len().is_none() && self.buffer.is_truncated() => {
+                self.nsi_idx = start + err.valid_up_to();
+                self.buffer.payload_idx = self.nsi_idx;
+                true
+            }
+            Err(_) => false,
+        }
+    }
 
-// #[cfg(test)]
-// mod test {
-//     use super::*;
-//     use heapless::consts::*;
-//     use heapless::spsc::Queue;
-//     #[test]
-//     fn ingress_syscall() {
-//         let system = {
-//             System::new(rtc, bms, nmgr, amgr)
-//         };
-//         let mut imgr = IngressManager::new();
-//         let mut data = vec![STX, b'S', PAYLOAD];
-//         for byte in "T00:00:00".bytes() {
-//             data.push(byte);
-//         }
-//         data.push(ETX);
-//         imgr.write(&data);
-//         imgr.process();
-
-//         assert_eq!(imgr.state, State::Wait);
-//     }
-// }
+    /// Feed received frame bytes into the frame CRC, holding back the last `CRC_LEN` as they may be the CRC itself.
+    /// The `ESC` in front of an escaped byte is held back with it, as it is only part of the CRC if the byte is.
+    /// Application images never carry a CRC, so their data is skipped
+    fn update_crc(&mut self, bytes: &[u8]) {
+        match self.state {
+            State::Wait | State::ApplicationStore => return,
+            _ => {}
+        }
+        for &byte in bytes {
+            if byte == ESC && !self.escaped {
+                continue;
+            }
+            if self.crc_tail_len == CRC_LEN {
+                let (escaped, oldest) = self.crc_tail[0];
+                if escaped {
+                    self.frame_crc = crc16_ccitt(self.frame_crc, &[ESC]);
+                }
+                self.frame_crc = crc16_ccitt(self.frame_crc, &[oldest]);
+                self.crc_tail.rotate_left(1);
+                self.crc_tail[CRC_LEN - 1] = (self.escaped, byte);
+            } else {
+                self.crc_tail[self.crc_tail_len] = (self.escaped, byte);
+                self.crc_tail_len += 1;
+            }
+        }
+    }
+
+    /// Removes the trailing big-endian CRC16 from the buffer, and checks it against the CRC of every byte of the
+    /// frame before it, from the type byte on, as they were received
+    fn verify_crc(&mut self) -> bool {
+        if self.crc_tail_len < CRC_LEN {
+            error!("Frame is too small to contain a CRC {:?}", self.buffer);
+            return false;
+        }
+        let expected = (u16::from(self.crc_tail[0].1) << 8) | u16::from(self.crc_tail[1].1);
+        // the CRC was read as it arrived, a truncated payload may only hold part of it, or none
+        let stored = CRC_LEN - self.overflowed.min(CRC_LEN);
+        if stored > self.buffer.len() {
+            error!("Buffer is too small to contain a CRC {:?}", self.buffer);
+            return false;
+        }
+        self.buffer.payload_idx -= stored;
+        if self.buffer.btype == Type::Notification {
+            self.nsi_idx -= stored;
+        }
+        let actual = self.frame_crc;
+        if actual != expected {
+            error!("Packet CRC failed, expected {:?} got {:?}", expected, actual);
+            return false;
+        }
+        true
+    }
+
+    /// Based on the type byte, determine the type of the incoming payload
+    fn determine_type(&mut self, type_byte: u8) -> Type {
+        self.buffer.btype = match type_byte {
+            b'N' => Type::Notification, /* NOTIFICATION i.e FB Msg */
+            b'S' => Type::Syscall,
+            b'D' => Type::NotificationDismiss, /* Dismiss a notification by id */
+            b'A' => Type::Application,  /* Load Application */
+            b'a' => { /* Load Application, sent as raw bytes rather than hex */
+                self.binary = true;
+                Type::Application
+            }
+            b'B' => { /* Load Application, with the image sent as base64 */
+                self.base64 = true;
+                Type::Application
+            }
+            _ => Type::Unknown,
+        };
+        self.buffer.btype
+    }
+
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crc::crc32::checksum_ieee;
+    use std::vec::Vec;
+    use crate::system::notification::{BUFF_COUNT, BUFF_SIZE};
+
+    fn application_frame(checksum: &str, image: &[u8]) -> Vec<u8> {
+        let mut data = vec![STX, b'A', PAYLOAD];
+        data.extend_from_slice(checksum.as_bytes());
+        data.push(PAYLOAD);
+        for byte in image {
+            data.extend_from_slice(format!("{:02X}", byte).as_bytes());
+        }
+        data.push(ETX);
+        data
+    }
+
+    #[test]
+    fn ingress_application() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let image = [1u8, 2, 3, 4];
+        let checksum = format!("{:08X}", checksum_ieee(&image));
+        imgr.write(&application_frame(&checksum, &image)).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert!(system.am().status().is_loaded);
+    }
+
+    #[test]
+    fn ingress_base64_application() {
+        // 5 and 4 bytes, neither a multiple of 3, padded and unpadded
+        let cases: [(&[u8], &[u8]); 4] = [
+            (&[1, 2, 3, 4, 5], b"AQIDBAU="),
+            (&[1, 2, 3, 4], b"AQIDBA=="),
+            (&[1, 2, 3, 4], b"AQIDBA"),
+            (&[0xFB, 0xFF, 0x00], b"+/8A"),
+        ];
+        for (image, encoded) in cases.iter() {
+            let mut system = System::mock();
+            let mut imgr: IngressManager = IngressManager::new();
+            let mut data = vec![STX, b'B', PAYLOAD];
+            data.extend_from_slice(format!("{:08X}", checksum_ieee(image)).as_bytes());
+            data.push(PAYLOAD);
+            data.extend_from_slice(encoded);
+            data.push(ETX);
+            imgr.write(&data).unwrap();
+            assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+            assert_eq!(system.am().status().ram_used, image.len());
+            assert!(system.am().status().is_loaded);
+        }
+
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        for encoded in [&b"AQ*D"[..], b"A==="].iter() {
+            let mut data = vec![STX, b'B', PAYLOAD];
+            data.extend_from_slice(b"00000000");
+            data.push(PAYLOAD);
+            data.extend_from_slice(encoded);
+            data.push(ETX);
+            imgr.write(&data).unwrap();
+            assert_eq!(imgr.process(&mut system), Err(IngressError::InvalidImage));
+        }
+    }
+
+    fn chunk_frame(flag: u8, image: &[u8], offset: usize, chunk: &[u8]) -> Vec<u8> {
+        let mut data = vec![STX, b'A', flag, PAYLOAD];
+        data.extend_from_slice(format!("{:08X}", checksum_ieee(image)).as_bytes());
+        data.push(PAYLOAD);
+        data.extend_from_slice(offset.to_string().as_bytes());
+        data.push(PAYLOAD);
+        for byte in chunk {
+            data.extend_from_slice(format!("{:02X}", byte).as_bytes());
+        }
+        data.push(ETX);
+        data
+    }
+
+    #[test]
+    fn ingress_application_chunks() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let image = [1u8, 2, 3, 4, 5, 6, 7, 8];
+
+        // out of order, the last chunk sent completes the image
+        imgr.write(&chunk_frame(CHUNK_FLAG, &image, 4, &image[4..])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        assert!(!system.am().status().is_loaded);
+        assert_eq!(system.am().status().ram_used, 8);
+        imgr.write(&chunk_frame(LAST_CHUNK_FLAG, &image, 0, &image[..4])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert!(system.am().status().is_loaded);
+
+        // a chunk of another application resets the slot, a resumed transfer keeps it
+        let other = [9u8, 10, 11, 12];
+        imgr.write(&chunk_frame(CHUNK_FLAG, &other, 0, &other[..2])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        assert_eq!(system.am().status().ram_used, 2);
+        imgr.write(&chunk_frame(LAST_CHUNK_FLAG, &other, 2, &other[2..])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert!(system.am().status().is_loaded);
+        assert_eq!(system.am().status().ram_used, 4);
+
+        // an invalid offset aborts the chunk
+        let mut frame = chunk_frame(CHUNK_FLAG, &other, 0, &other);
+        frame[13] = b'x';
+        imgr.write(&frame).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+    }
+
+    #[test]
+    fn ingress_application_slot() {
+        use crate::application::application_manager::Ram;
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let ram: &'static mut [u8] = Box::leak(vec![0u8; 64].into_boxed_slice());
+        system.am().add_slot(Ram::new(ram)).unwrap();
+        let image = [1u8, 2, 3, 4];
+        let mut frame = application_frame(&format!("{:08X}", checksum_ieee(&image)), &image);
+        frame.insert(2, b'1');
+        imgr.write(&frame).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert_eq!(system.am().load_slot(), 1);
+        assert!(!system.am().status().is_loaded);
+        system.am().switch_slot(1).unwrap();
+        assert!(system.am().status().is_loaded);
+
+        // there is no third slot
+        frame[2] = b'2';
+        imgr.write(&frame).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(system.am().select_slot(2), Err(AmError::InvalidSlot(2)));
+    }
+
+    #[test]
+    fn ingress_application_running_slot() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let image = [1u8, 2, 3, 4];
+        imgr.write(&application_frame(&format!("{:08X}", checksum_ieee(&image)), &image)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        system.am().set_running(true);
+
+        // neither a new application nor a chunk may overwrite the running application
+        let other = [5u8, 6, 7, 8];
+        let checksum = format!("{:08X}", checksum_ieee(&other));
+        imgr.write(&application_frame(&checksum, &other)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        imgr.write(&chunk_frame(CHUNK_FLAG, &other, 0, &other)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        let status = system.am().status();
+        assert!(status.is_running);
+        assert!(status.is_loaded);
+        assert_eq!(status.ram_used, image.len());
+        assert_eq!(system.am().staged_checksum(), Some(checksum_ieee(&image)));
+
+        // once stopped it can be replaced
+        system.am().set_running(false);
+        imgr.write(&application_frame(&checksum, &other)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert_eq!(system.am().staged_checksum(), Some(checksum_ieee(&other)));
+    }
+
+    #[test]
+    fn ingress_application_metadata() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let image = [1u8, 2, 3, 4];
+        let mut data = vec![STX, b'A', b'M', PAYLOAD];
+        data.extend_from_slice(b"demo");
+        data.push(PAYLOAD);
+        data.extend_from_slice(b"1.0.2");
+        data.extend_from_slice(&application_frame(&format!("{:08X}", checksum_ieee(&image)), &image)[2..]);
+        imgr.write(&data).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert!(system.am().status().is_loaded);
+        assert_eq!(system.am().name(), Some("demo"));
+        assert_eq!(system.am().version(), Some("1.0.2"));
+
+        // metadata is optional
+        imgr.write(&application_frame(&format!("{:08X}", checksum_ieee(&image)), &image)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert_eq!(system.am().name(), None);
+    }
+
+    /// An application frame with the first image byte corrupted
+    fn corrupt_application_frame(image: &[u8]) -> Vec<u8> {
+        let mut data = application_frame(&format!("{:08X}", checksum_ieee(image)), image);
+        let start = data.len() - image.len() * 2 - 1;
+        data[start] = b'Z';
+        data
+    }
+
+    #[test]
+    fn ingress_application_error_budget() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.set_error_budget(1);
+        // the corrupt byte is padded with 0, so the checksum still matches
+        imgr.write(&corrupt_application_frame(&[0u8, 1, 2, 3])).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert_eq!(imgr.hex_errors(), 1);
+        assert_eq!(system.am().status().ram_used, 4);
+        assert!(system.am().status().is_loaded);
+    }
+
+    #[test]
+    fn ingress_application_no_error_budget() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&corrupt_application_frame(&[0u8, 1, 2, 3])).unwrap();
+
+        assert!(imgr.process(&mut system).is_err());
+        assert_eq!(system.am().status().ram_used, 0);
+        assert!(!system.am().status().is_loaded);
+    }
+
+    #[test]
+    fn ingress_binary_application() {
+        // include the framing bytes in the image to make sure they are escaped
+        let image = [STX, ETX, PAYLOAD, ESC, 0xFF, 0x00, b'A'];
+        let checksum = checksum_ieee(&image).to_be_bytes();
+        let hex_frame = application_frame(&format!("{:08X}", checksum_ieee(&image)), &image);
+
+        let mut escaped = [0u8; 32];
+        let mut binary_frame = vec![STX, b'a', PAYLOAD];
+        let len = escape_into(&checksum, &mut escaped).unwrap();
+        binary_frame.extend_from_slice(&escaped[..len]);
+        binary_frame.push(PAYLOAD);
+        let len = escape_into(&image, &mut escaped).unwrap();
+        binary_frame.extend_from_slice(&escaped[..len]);
+        binary_frame.push(ETX);
+        assert!(binary_frame.len() < hex_frame.len());
+
+        // both encodings must verify against the same checksum, hence store the same image
+        for frame in [hex_frame, binary_frame].iter() {
+            let mut system = System::mock();
+            let mut imgr: IngressManager = IngressManager::new();
+            imgr.write(frame).unwrap();
+            assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+            assert!(system.am().status().is_loaded);
+        }
+    }
+
+    #[test]
+    fn ingress_fast_path_matches_slow_path() {
+        /// Frame the sections, escaping every byte when `slow` so that no run can take the fast path
+        fn frame(btype: u8, sections: &[&[u8]], slow: bool) -> Vec<u8> {
+            let mut data = vec![STX, btype];
+            for section in sections {
+                data.push(PAYLOAD);
+                for byte in section.iter() {
+                    if slow || [STX, ETX, PAYLOAD, ESC].contains(byte) {
+                        data.push(ESC);
+                    }
+                    data.push(*byte);
+                }
+            }
+            data.push(ETX);
+            data
+        }
+
+        // an image that fits, and one that overflows the application ram
+        for &len in [700, 1100].iter() {
+            let image: Vec<u8> = (0..len).map(|idx| (idx * 7) as u8).collect();
+            let checksum = checksum_ieee(&image).to_be_bytes();
+            let mut results = Vec::new();
+            for &slow in [false, true].iter() {
+                let mut system = System::mock();
+                let mut imgr: IngressManager = IngressManager::new();
+                let data = frame(b'a', &[&checksum, &image], slow);
+                let mut result = None;
+                for chunk in data.chunks(100) {
+                    imgr.write(chunk).unwrap();
+                    if let Some(btype) = imgr.process(&mut system).unwrap() {
+                        result = Some(btype);
+                    }
+                }
+                let status = system.am().status();
+                results.push((result, status.is_loaded, status.ram_used));
+            }
+            assert_eq!(results[0], results[1]);
+            assert_eq!(results[0].1, len == 700);
+        }
+
+        // a syscall that fits, and one that overflows the buffer
+        for &len in [8, BUFF_SIZE + 8].iter() {
+            let payload: Vec<u8> = b"VB200,1".iter().cloned().chain(core::iter::repeat(b'0').take(len - 7)).collect();
+            let mut results = Vec::new();
+            for &slow in [false, true].iter() {
+                let mut system = System::mock();
+                let mut imgr: IngressManager = IngressManager::new();
+                let mut result = None;
+                for chunk in frame(b'S', &[&payload], slow).chunks(100) {
+                    imgr.write(chunk).unwrap();
+                    if let Some(btype) = imgr.process(&mut system).unwrap() {
+                        result = Some(btype);
+                    }
+                }
+                let mut out = [0u8; 32];
+                let len = imgr.read(&mut out);
+                results.push((result, out[..len].to_vec(), imgr.buffer.len(), imgr.buffer.is_truncated()));
+            }
+            assert_eq!(results[0], results[1]);
+            assert_eq!(results[0].3, len > BUFF_SIZE);
+        }
+    }
+
+    #[test]
+    fn ingress_application_bad_checksum() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&application_frame("00000000", &[1u8, 2, 3, 4])).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Err(IngressError::ChecksumMismatch));
+        assert_eq!(imgr.state, State::Wait);
+        assert!(!system.am().status().is_loaded);
+    }
+
+    #[test]
+    fn ingress_application_corrupt_byte() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let image = [0x10u8, 0x20, 0x30, 0x40, 0x50];
+        let checksum = format!("{:08X}", checksum_ieee(&image));
+        let mut corrupt = image;
+        corrupt[2] ^= 0x01;
+        imgr.write(&application_frame(&checksum, &corrupt)).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Err(IngressError::ChecksumMismatch));
+        let status = system.am().status();
+        assert!(!status.is_loaded);
+        assert_eq!(status.ram_used, 0);
+        assert_eq!(system.am().verify(), Err(AmError::NotStaged));
+
+        imgr.write(&application_frame(&checksum, &image)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert!(system.am().status().is_loaded);
+    }
+
+    #[test]
+    fn ingress_application_checksum_too_short() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&application_frame("0000", &[1u8, 2, 3, 4])).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.state, State::Wait);
+        assert_eq!(system.am().status().ram_used, 0);
+    }
+
+    #[test]
+    fn ingress_application_checksum_too_long() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&application_frame("0000000000", &[1u8, 2, 3, 4])).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.state, State::Wait);
+        assert_eq!(system.am().status().ram_used, 0);
+    }
+
+    #[test]
+    fn ingress_escaped_notification() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let body = [b'a', STX, ETX, PAYLOAD, ESC, b'b'];
+        let mut escaped = [0u8; 16];
+        let len = escape_into(&body, &mut escaped).unwrap();
+        let mut data = vec![STX, b'N', PAYLOAD];
+        data.extend_from_slice(b"src");
+        data.push(PAYLOAD);
+        data.extend_from_slice(b"title");
+        data.push(PAYLOAD);
+        data.extend_from_slice(&escaped[..len]);
+        data.push(ETX);
+        imgr.write(&data).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        assert_eq!(imgr.state, State::Wait);
+        system.nm().peek_notification(0, |notification| {
+            assert_eq!(notification.body().as_bytes(), &body);
+        });
+    }
+
+    #[test]
+    fn ingress_escape_split_across_writes() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let mut data = vec![STX, b'N', PAYLOAD];
+        data.extend_from_slice(b"src");
+        data.push(PAYLOAD);
+        data.extend_from_slice(b"title");
+        data.extend_from_slice(&[PAYLOAD, b'a', ESC]);
+        imgr.write(&data).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+
+        // the escaped ETX arrives in the next chunk, the packet ends on the real one
+        imgr.write(&[ETX, b'b', ETX]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_notification(0, |notification| {
+            assert_eq!(notification.body().as_bytes(), &[b'a', ETX, b'b']);
+        });
+    }
+
+    fn crc_notification_frame(crc: u16) -> Vec<u8> {
+        // the CRC covers the type, flag and separators as well as the sections, "123", "456" and "789"
+        let mut data = vec![STX, b'N', CRC_FLAG, PAYLOAD];
+        data.extend_from_slice(b"123");
+        data.push(PAYLOAD);
+        data.extend_from_slice(b"456");
+        data.push(PAYLOAD);
+        data.extend_from_slice(b"789");
+        data.extend_from_slice(&crc.to_be_bytes());
+        data.push(ETX);
+        data
+    }
+
+    #[test]
+    fn ingress_notification_priority() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let mut data = notification_frame(&["src", "low", "body"]);
+        let mut urgent = notification_frame(&["src", "urgent", "body"]);
+        urgent.insert(2, b'7');
+        data.extend_from_slice(&urgent);
+        imgr.write(&data).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_by_priority(0, |notification| {
+            assert_eq!(notification.title(), "urgent");
+            assert_eq!(notification.priority(), 7);
+        });
+        system.nm().peek_by_priority(1, |notification| {
+            assert_eq!(notification.priority(), DEFAULT_PRIORITY);
+        });
+    }
+
+    #[test]
+    fn ingress_notification_timestamp() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let rtc_timestamp = system.timestamp();
+        for (timestamp, expected) in [("1700000000", 1_700_000_000), ("yesterday", rtc_timestamp), ("", rtc_timestamp)].iter() {
+            let mut data = notification_frame(&[timestamp, "src", "title", "body"]);
+            data.insert(2, TIMESTAMP_FLAG);
+            imgr.write(&data).unwrap();
+            assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+            let index = system.nm().idx() - 1;
+            system.nm().peek_notification(index, |notification| {
+                assert_eq!(notification.timestamp(), *expected);
+                assert_eq!((notification.source(), notification.title(), notification.body()), ("src", "title", "body"));
+            });
+        }
+
+        // without the flag the time of arrival is used
+        imgr.write(&notification_frame(&["src", "title", "body"])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_notification(3, |notification| assert_eq!(notification.timestamp(), rtc_timestamp));
+
+        // no sections at all, or only the timestamp
+        system.nm().clear_all();
+        imgr.write(&[STX, b'N', ETX]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        imgr.write(&[STX, b'N', TIMESTAMP_FLAG, PAYLOAD, b'1', ETX]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(system.nm().idx(), 0);
+    }
+
+    /// A syscall frame sent with its length
+    fn length_frame(syscall: &str) -> Vec<u8> {
+        let mut data = vec![STX, b'S', LENGTH_FLAG];
+        for byte in (syscall.len() as u16 + 1).to_be_bytes().iter() {
+            if [STX, ETX, PAYLOAD, ESC].contains(byte) {
+                data.push(ESC);
+            }
+            data.push(*byte);
+        }
+        data.push(PAYLOAD);
+        data.extend_from_slice(syscall.as_bytes());
+        data.push(ETX);
+        data
+    }
+
+    #[test]
+    fn ingress_length_prefixed() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let data = length_frame("T01:02:03");
+
+        // nothing is parsed until the whole frame has arrived
+        imgr.write(&data[..4]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        imgr.write(&data[4..9]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        assert_eq!(imgr.state(), IngressState::Flags);
+        assert_eq!(imgr.rb.len(), 4);
+        imgr.write(&data[9..]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        assert_eq!(system.rtc().get_time().seconds, 3);
+
+        // a lost ETX does not take the next frame with it
+        let mut data = length_frame("T04:05:06");
+        data.pop();
+        data.extend_from_slice(&[STX, b'S', PAYLOAD, b'V', ETX]);
+        imgr.write(&data).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        assert_eq!(system.rtc().get_time().seconds, 6);
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        assert_eq!(imgr.stats().partial_resets, 0);
+
+        // an ETX before the end of the frame
+        let mut data = length_frame("T07:08:09");
+        data[4] = 16;
+        data.extend_from_slice(&[STX, b'S', PAYLOAD, b'V', ETX]);
+        imgr.write(&data).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.state(), IngressState::Wait);
+        assert_eq!(system.rtc().get_time().seconds, 6);
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+
+        // a length byte that is also a framing byte is escaped
+        let data = length_frame("t");
+        assert_eq!(&data[3..5], &[0, ESC]);
+        imgr.write(&data).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+    }
+
+    #[test]
+    fn ingress_length_prefixed_timeout() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.tick(0);
+        let data = length_frame("T01:02:03");
+        imgr.write(&data[..6]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+
+        // bytes of a frame waiting on its length keep it alive
+        imgr.tick(PARTIAL_TIMEOUT_MS);
+        imgr.write(&data[6..8]).unwrap();
+        imgr.tick(PARTIAL_TIMEOUT_MS * 2);
+        assert_eq!(imgr.state(), IngressState::Flags);
+        imgr.write(&data[8..]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+
+        // while other frames only time out on the bytes that are parsed
+        imgr.write(&[STX, b'S', PAYLOAD, b'T']).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        imgr.tick(PARTIAL_TIMEOUT_MS * 3);
+        imgr.write(&[b'0']).unwrap();
+        imgr.tick(PARTIAL_TIMEOUT_MS * 3 + 1);
+        assert_eq!(imgr.state(), IngressState::Wait);
+    }
+
+    #[test]
+    fn ingress_crc_valid() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&crc_notification_frame(0x2BA7)).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        assert_eq!(system.nm().idx(), 1);
+        system.nm().peek_notification(0, |notification| {
+            assert_eq!(notification.body(), "789");
+        });
+    }
+
+    #[test]
+    fn ingress_crc_required() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.set_require_crc(true);
+
+        imgr.write(&notification_frame(&["src", "title", "body"])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        imgr.write(&[STX, b'S', PAYLOAD, b'T', b'0', b'0', b':', b'0', b'0', b':', b'0', b'0', ETX]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.stats().checksum_errors, 2);
+        assert_eq!(imgr.state, State::Wait);
+
+        // a corrupted payload byte, then a corrupted CRC byte
+        let mut corrupt = crc_notification_frame(0x2BA7);
+        corrupt[5] = b'4';
+        imgr.write(&corrupt).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        imgr.write(&crc_notification_frame(0x3BA7)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.stats().checksum_errors, 4);
+        assert_eq!(system.nm().idx(), 0);
+
+        imgr.write(&crc_notification_frame(0x2BA7)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        assert_eq!(system.nm().idx(), 1);
+    }
+
+    #[test]
+    fn ingress_crc_invalid() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&crc_notification_frame(0x2BA6)).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.state, State::Wait);
+        assert_eq!(system.nm().idx(), 0);
+
+    }
+
+    /// A notification frame with a correct CRC over everything after the STX
+    fn crc_frame(head: &[u8], sections: &[&[u8]]) -> Vec<u8> {
+        let mut frame = head.to_vec();
+        for section in sections {
+            frame.push(PAYLOAD);
+            frame.extend_from_slice(section);
+        }
+        let crc = crc16_ccitt(CRC_INIT, &frame).to_be_bytes();
+        let mut escaped = [0u8; 2 * CRC_LEN];
+        let len = escape_into(&crc, &mut escaped).unwrap();
+        frame.extend_from_slice(&escaped[..len]);
+        frame.insert(0, STX);
+        frame.push(ETX);
+        frame
+    }
+
+    /// Write `frame` a piece at a time, processing each piece
+    fn write_all(imgr: &mut IngressManager, system: &mut System, frame: &[u8]) -> Result<Option<Type>, IngressError> {
+        let mut result = Ok(None);
+        for chunk in frame.chunks(256) {
+            imgr.write(chunk).unwrap();
+            result = imgr.process(system);
+        }
+        result
+    }
+
+    #[test]
+    fn ingress_crc_covers_frame() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let frame = crc_frame(&[b'N', b'5', CRC_FLAG], &[b"src", b"title", b"body"]);
+
+        // a flipped flag or separator is caught even though the section bytes are intact
+        for (idx, byte) in [(2, b'9'), (8, b'x')].iter() {
+            let mut corrupt = frame.clone();
+            corrupt[*idx] = *byte;
+            imgr.write(&corrupt).unwrap();
+            assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        }
+        // as is a flipped type
+        let mut flipped = crc_frame(&[b'S', CRC_FLAG], &[b"t"]);
+        flipped[1] = b'N';
+        imgr.write(&flipped).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.stats().checksum_errors, 3);
+        assert_eq!(system.nm().idx(), 0);
+
+        imgr.write(&frame).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_notification(0, |notification| {
+            assert_eq!(notification.title(), "title");
+            assert_eq!(notification.body(), "body");
+            assert_eq!(notification.priority(), 5);
+        });
+
+        // escaped bytes are covered as sent, so are syscalls
+        let frame = crc_frame(&[b'N', CRC_FLAG], &[b"src", &[ESC, ETX], b"body"]);
+        imgr.write(&frame).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        imgr.write(&crc_frame(&[b'S', CRC_FLAG], &[b"t"])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+
+        // as is a CRC byte that collides with a framing byte, without its ESC
+        let frame = crc_frame(&[b'N', CRC_FLAG], &[b"src", b"title", b"b24"]);
+        assert_eq!(&frame[frame.len() - 4..], &[0x3C, ESC, ESC, ETX]);
+        imgr.write(&frame).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        let last = system.nm().idx() - 1;
+        system.nm().peek_notification(last, |notification| {
+            assert_eq!(notification.body(), "b24");
+        });
+
+        // the CRC is read as it arrives, so a truncated notification can still be checked
+        let body = "x".repeat(BUFF_SIZE);
+        let frame = crc_frame(&[b'N', CRC_FLAG], &[b"src", b"title", body.as_bytes()]);
+        assert_eq!(write_all(&mut imgr, &mut system, &frame), Ok(Some(Type::Notification)));
+        let mut corrupt = frame.clone();
+        corrupt[frame.len() - 10] = b'y';
+        assert_eq!(write_all(&mut imgr, &mut system, &corrupt), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.stats().checksum_errors, 4);
+    }
+
+    #[test]
+    fn crc16_ccitt_check() {
+        assert_eq!(crc16_ccitt(CRC_INIT, b"123456789"), 0x29B1);
+        assert_eq!(crc16_ccitt(crc16_ccitt(CRC_INIT, b"1234"), b"56789"), 0x29B1);
+        assert_eq!(crc16_ccitt(CRC_INIT, &[]), CRC_INIT);
+    }
+
+    #[test]
+    fn max_frame_size() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.set_max_frame(Some(64));
+
+        // a runaway notification body, a syscall payload on the fast path and a hex application
+        let mut notification = vec![STX, b'N', PAYLOAD, b's', PAYLOAD, b't', PAYLOAD];
+        notification.extend_from_slice(&[b'b'; 100]);
+        let mut syscall = vec![STX, b'S', PAYLOAD];
+        syscall.extend_from_slice(&[b'0'; 100]);
+        let application = application_frame("00000000", &[0u8; 50]);
+        for frame in [notification, syscall, application[..application.len() - 1].to_vec()].iter() {
+            imgr.write(frame).unwrap();
+            assert_eq!(imgr.process(&mut system), Err(IngressError::FrameTooLong));
+            assert_eq!(imgr.state(), IngressState::Wait);
+            // the rest of the frame is ignored
+            imgr.write(&[b'x', ETX]).unwrap();
+            assert_eq!(imgr.process(&mut system), Ok(None));
+        }
+        assert_eq!(imgr.stats().frames_too_long, 3);
+        assert_eq!(system.nm().idx(), 0);
+
+        // a frame of exactly the maximum is accepted
+        let mut data = vec![STX, b'S', PAYLOAD];
+        data.extend_from_slice(b"B40");
+        data.extend_from_slice(&[b' '; 59]);
+        data.push(ETX);
+        imgr.write(&data).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+    }
+
+    #[test]
+    fn match_results() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let mut matched = |data: &[u8]| {
+            imgr.write(data).unwrap();
+            imgr.match_rb(&mut system)
+        };
+
+        assert_eq!(matched(&[STX, b'S', PAYLOAD, b't']), MatchResult::Incomplete);
+        assert_eq!(matched(&[ETX]), MatchResult::Completed(Type::Syscall));
+        // a stray ETX does not complete the last frame again
+        assert_eq!(matched(&[ETX]), MatchResult::Incomplete);
+        assert_eq!(matched(&[STX, b'Z', PAYLOAD, b't', ETX]), MatchResult::Aborted(AbortReason::UnknownType));
+        assert_eq!(matched(&[STX, b'S', b'x', PAYLOAD, b't', ETX]), MatchResult::Aborted(AbortReason::Invalid));
+        assert_eq!(matched(&crc_notification_frame(0x2BA6)), MatchResult::Aborted(AbortReason::CrcMismatch));
+        assert_eq!(matched(&application_frame("ZZ", &[1])), MatchResult::Aborted(AbortReason::InvalidImage));
+
+        imgr.set_capture_unknown(true);
+        imgr.write(&[STX, b'Z', PAYLOAD, b't', ETX]).unwrap();
+        assert_eq!(imgr.match_rb(&mut system), MatchResult::Completed(Type::Unknown));
+    }
+
+    #[test]
+    fn ingress_partial_timeout() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.tick(0);
+        imgr.write(&[STX, b'S', PAYLOAD, b'T']).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        assert_eq!(imgr.state, State::Payload);
+
+        imgr.tick(PARTIAL_TIMEOUT_MS);
+        assert_eq!(imgr.state, State::Payload);
+
+        imgr.tick(PARTIAL_TIMEOUT_MS + 1);
+        assert_eq!(imgr.state, State::Wait);
+        assert_eq!(imgr.dropped_partial(), 1);
+    }
+
+    #[test]
+    fn ingress_application_timeout() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::with_timeout(500);
+        imgr.tick(1000);
+        let image = [0xAB; 16];
+        let data = application_frame(&format!("{:08X}", checksum_ieee(&image)), &image);
+        // the link drops half way through the image
+        imgr.write(&data[..data.len() / 2]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        assert_eq!(imgr.state, State::ApplicationStore);
+
+        imgr.tick(1501);
+        assert_eq!(imgr.state, State::Wait);
+        assert_eq!((imgr.hex_idx, imgr.nsi_idx, imgr.buffer.len()), (0, 0, 0));
+        assert_eq!(imgr.dropped_partial(), 1);
+
+        // the rest of the image arriving late is ignored, and the transfer can be retried
+        imgr.write(&data[data.len() / 2..]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        imgr.write(&data).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+    }
+
+    fn notification_frame(sections: &[&str]) -> Vec<u8> {
+        let mut data = vec![STX, b'N'];
+        for section in sections {
+            data.push(PAYLOAD);
+            data.extend_from_slice(section.as_bytes());
+        }
+        data.push(ETX);
+        data
+    }
+
+    #[test]
+    fn ingress_notification_sections() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&notification_frame(&["src", "title", "body", "1234"])).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_notification(0, |notification| {
+            assert_eq!(notification.section_count(), 4);
+            assert_eq!(notification.source(), "src");
+            assert_eq!(notification.title(), "title");
+            assert_eq!(notification.body(), "body");
+            assert_eq!(notification.section(3), Some("1234"));
+            assert_eq!(notification.section(4), None);
+        });
+    }
+
+    #[test]
+    fn ingress_notification_too_many_sections() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&notification_frame(&["s"; 9])).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(system.nm().idx(), 0);
+    }
+
+    #[test]
+    fn ingress_notification_truncated() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager<U1024> = IngressManager::new();
+        let body: std::string::String = core::iter::repeat('x').take(BUFF_SIZE).collect();
+        imgr.write(&notification_frame(&["src", "title", &body])).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_notification(0, |notification| {
+            assert!(notification.is_truncated());
+            assert_eq!(notification.source(), "src");
+            assert_eq!(notification.title(), "title");
+            assert_eq!(notification.body().len(), BUFF_SIZE - "srctitle".len());
+        });
+
+        // the next packet is unaffected
+        imgr.write(&notification_frame(&["src", "title", "body"])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_notification(1, |notification| {
+            assert!(!notification.is_truncated());
+            assert_eq!(notification.body(), "body");
+        });
+    }
+
+    #[test]
+    fn ingress_syscall_error_response() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&[STX, b'S', PAYLOAD, b'X', ETX]).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        let mut out = [0u8; 32];
+        let len = imgr.read(&mut out);
+        let mut expected = vec![STX, RESPONSE, PAYLOAD];
+        expected.extend_from_slice(b"EUnknownCommand");
+        expected.push(ETX);
+        assert_eq!(&out[..len], &expected[..]);
+        assert_eq!(imgr.read(&mut out), 0);
+    }
+
+    #[test]
+    fn ingress_syscall_invalid_utf8() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&[STX, b'S', PAYLOAD, b'V', 0xC3, ETX]).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Err(IngressError::InvalidUtf8));
+        assert_eq!(imgr.read(&mut [0u8; 32]), 0); // dropped without a response
+        assert_eq!(imgr.state(), IngressState::Wait);
+    }
+
+    #[test]
+    fn ingress_syscall_get_battery() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&[STX, b'S', PAYLOAD, b'G', b'B', ETX]).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        let mut out = [0u8; 32];
+        let len = imgr.read(&mut out);
+        let mut expected = vec![STX, RESPONSE, PAYLOAD];
+        // the mock battery has not been measured
+        expected.extend_from_slice(b"B0,0");
+        expected.push(ETX);
+        assert_eq!(&out[..len], &expected[..]);
+    }
+
+    #[test]
+    fn ingress_syscall_get_version() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&[STX, b'S', PAYLOAD, b'V', ETX]).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        let mut out = [0u8; 32];
+        let len = imgr.read(&mut out);
+        let mut expected = vec![STX, RESPONSE, PAYLOAD, b'V'];
+        expected.extend_from_slice(env!("CARGO_PKG_VERSION").as_bytes());
+        expected.push(ETX);
+        assert_eq!(&out[..len], &expected[..]);
+    }
+
+    /// Process and read the outbound data until there is none left, returning the payload of each frame
+    fn responses(imgr: &mut IngressManager, system: &mut System) -> Vec<String> {
+        let mut out = Vec::new();
+        loop {
+            imgr.process(system).unwrap();
+            let mut chunk = [0u8; 64];
+            let len = imgr.read(&mut chunk);
+            if len == 0 {
+                break;
+            }
+            out.extend_from_slice(&chunk[..len]);
+        }
+        out.split(|byte| *byte == ETX)
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| {
+                assert_eq!(&frame[..3], &[STX, RESPONSE, PAYLOAD]);
+                String::from_utf8(frame[3..].to_vec()).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ingress_application_resume() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let image = [1u8, 2, 3, 4, 5, 6, 7, 8];
+        let checksum = format!("{:08X}", checksum_ieee(&image));
+        imgr.write(&[STX, b'S', PAYLOAD, b'A', b'O', ETX]).unwrap();
+        assert_eq!(responses(&mut imgr, &mut system), ["O0"]);
+
+        // the transfer is interrupted, after a chunk past a gap
+        imgr.write(&chunk_frame(CHUNK_FLAG, &image, 0, &image[..4])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        imgr.write(&chunk_frame(CHUNK_FLAG, &image, 6, &image[6..])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        imgr.write(&[STX, b'S', PAYLOAD, b'A', b'O', ETX]).unwrap();
+        assert_eq!(responses(&mut imgr, &mut system), [format!("O4,{}", checksum)]);
+
+        // resumed from the offset the watch asked for
+        imgr.write(&chunk_frame(LAST_CHUNK_FLAG, &image, 4, &image[4..])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert!(system.am().status().is_loaded);
+        imgr.write(&[STX, b'S', PAYLOAD, b'A', b'O', ETX]).unwrap();
+        assert_eq!(responses(&mut imgr, &mut system), [format!("O8,{}", checksum)]);
+    }
+
+    #[test]
+    fn ingress_syscall_list_notifications() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        for (now, title) in [(100, "first"), (200, "second"), (300, "third")].iter() {
+            system.nm().tick(*now);
+            imgr.write(&notification_frame(&["src", title, "body"])).unwrap();
+            imgr.process(&mut system).unwrap();
+        }
+        imgr.read(&mut [0u8; 64]);
+
+        imgr.write(&[STX, b'S', PAYLOAD, b'L', b'N', ETX]).unwrap();
+        assert_eq!(responses(&mut imgr, &mut system), [
+            "L3",
+            "N0,0,100,src,first",
+            "N1,1,200,src,second",
+            "N2,2,300,src,third",
+        ]);
+    }
+
+    #[test]
+    fn ingress_notification_id_response() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&notification_frame(&["src", "first", "body"])).unwrap();
+        imgr.write(&notification_frame(&["src", "second", "body"])).unwrap();
+        assert_eq!(responses(&mut imgr, &mut system), ["I0", "I1"]);
+    }
+
+    #[test]
+    fn ingress_notification_dismiss() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&notification_frame(&["src", "first", "body"])).unwrap();
+        imgr.write(&notification_frame(&["src", "second", "body"])).unwrap();
+        assert_eq!(responses(&mut imgr, &mut system), ["I0", "I1"]);
+
+        imgr.write(&[STX, b'D', PAYLOAD, b'0', ETX]).unwrap();
+        assert_eq!(imgr.process_with_ack(&mut system), Some([STX, ACK, b'D', ETX]));
+        assert_eq!(system.nm().idx(), 1);
+        system.nm().peek_notification(0, |notification| assert_eq!(notification.title(), "second"));
+
+        // dismissing an unknown or malformed id leaves the notifications alone
+        imgr.write(&[STX, b'D', PAYLOAD, b'0', ETX]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::NotificationDismiss)));
+        imgr.write(&[STX, b'D', PAYLOAD, b'x', ETX]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::NotificationDismiss)));
+        assert_eq!(system.nm().idx(), 1);
+
+        imgr.write(&crc_frame(&[b'D', CRC_FLAG], &[b"1"])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::NotificationDismiss)));
+        assert_eq!(system.nm().idx(), 0);
+    }
+
+    #[test]
+    fn ingress_syscall_list_notifications_chunked() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        // more than the outbound queue holds, as each title is truncated to 64 bytes
+        let title = "é".repeat(40);
+        let count = BUFF_COUNT;
+        for _ in 0..count {
+            imgr.write(&notification_frame(&["a long source name", &title, "body"])).unwrap();
+            imgr.process(&mut system).unwrap();
+            imgr.read(&mut [0u8; 16]);
+        }
+
+        imgr.write(&[STX, b'S', PAYLOAD, b'L', b'N', ETX]).unwrap();
+        let listed = responses(&mut imgr, &mut system);
+        assert_eq!(listed.len(), count + 1);
+        assert_eq!(listed[0], format!("L{}", count));
+        for (index, entry) in listed[1..].iter().enumerate() {
+            assert_eq!(entry, &format!("N{},{},0,a long source na,{}", index, index, "é".repeat(32)));
+        }
+    }
+
+    #[test]
+    fn ingress_syscall_get_time() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&[STX, b'S', PAYLOAD, b't', ETX]).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        let mut out = [0u8; 32];
+        let len = imgr.read(&mut out);
+        let mut expected = vec![STX, RESPONSE, PAYLOAD];
+        expected.extend_from_slice(b"T00:00:00");
+        expected.push(ETX);
+        assert_eq!(&out[..len], &expected[..]);
+    }
+
+    #[test]
+    fn ingress_acks() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&[STX, b'S', PAYLOAD, b'V', ETX]).unwrap();
+        assert_eq!(imgr.process_with_ack(&mut system), Some([STX, ACK, b'S', ETX]));
+        // nothing more to process
+        assert_eq!(imgr.process_with_ack(&mut system), None);
+
+        imgr.write(&application_frame("DEADBEEF", &[0xAB; 16])).unwrap();
+        assert_eq!(imgr.process_with_ack(&mut system), Some([STX, NACK, NackCode::Checksum as u8, ETX]));
+
+        imgr.write(&[STX, b'?', PAYLOAD, b'x', ETX]).unwrap();
+        assert_eq!(imgr.process_with_ack(&mut system), Some([STX, NACK, NackCode::UnknownType as u8, ETX]));
+        imgr.write(&crc_notification_frame(0x2BA6)).unwrap();
+        assert_eq!(imgr.process_with_ack(&mut system), Some([STX, NACK, NackCode::Checksum as u8, ETX]));
+
+        imgr.write(&notification_frame(&["src", "title", "body"])).unwrap();
+        assert_eq!(imgr.process_with_ack(&mut system), Some([STX, ACK, b'N', ETX]));
+        // dropping a notification from a muted source is not a failure
+        system.nm().mute_source("src").unwrap();
+        imgr.write(&notification_frame(&["src", "title", "body"])).unwrap();
+        assert_eq!(imgr.process_with_ack(&mut system), Some([STX, ACK, b'N', ETX]));
+    }
+
+    #[test]
+    fn ingress_ack_queued() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&[STX, b'S', PAYLOAD, b'V', ETX]).unwrap();
+        let response = imgr.process_with_ack(&mut system).unwrap();
+        imgr.queue_response(&response).unwrap();
+
+        // the syscall response is sent first
+        let mut out = [0u8; 64];
+        let len = imgr.read(&mut out);
+        assert_eq!(&out[len - 4..len], &[STX, ACK, b'S', ETX]);
+        assert_eq!(&out[..3], &[STX, RESPONSE, PAYLOAD]);
+
+        // all or nothing
+        while imgr.queue_response(&response).is_ok() {}
+        assert_eq!(imgr.queue_response(&response), Err(IngressError::BufferFull));
+        assert_eq!(imgr.read(&mut [0u8; 4096]) % 4, 0);
+    }
+
+    #[test]
+    fn ingress_stats() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        // unknown type, then a packet interrupted by another
+        imgr.write(&[STX, b'?', PAYLOAD, b'x', ETX]).unwrap();
+        imgr.write(&[STX, b'N', PAYLOAD, b's']).unwrap();
+        imgr.write(&notification_frame(&["src", "title", "body"])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+
+        imgr.write(&crc_notification_frame(0x2BA6)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        imgr.write(&crc_notification_frame(0x2BA7)).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+
+        // more than the ring buffer can hold
+        let written = imgr.write(&[0u8; 600]).unwrap();
+        assert!(written < 600);
+        assert_eq!(imgr.write(&[0u8; 10]), Err(IngressError::BufferFull));
+        imgr.reset();
+
+        assert_eq!(imgr.stats(), IngressStats {
+            packets_ok: 2,
+            unknown_type: 1,
+            partial_resets: 1,
+            checksum_errors: 1,
+            overflow_drops: 2,
+            frames_too_long: 0,
+        });
+        imgr.reset_stats();
+        assert_eq!(imgr.stats(), IngressStats::default());
+    }
+
+    #[test]
+    fn ingress_reset() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let data = application_frame("00000000", &[0xAB; 16]);
+        imgr.write(&data[..data.len() / 2]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(None));
+        assert_eq!(imgr.state(), IngressState::ApplicationStore);
+        imgr.write(&data[data.len() / 2..]).unwrap(); // never processed
+
+        imgr.reset();
+        assert_eq!(imgr.state(), IngressState::Wait);
+
+        imgr.write(&notification_frame(&["src", "title", "body"])).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_notification(0, |notification| {
+            assert_eq!(notification.source(), "src");
+            assert_eq!(notification.title(), "title");
+            assert_eq!(notification.body(), "body");
+        });
+        assert_eq!(imgr.stats().partial_resets, 0);
+    }
+
+    #[test]
+    fn ingress_custom_framing() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::with_framing(FramingConfig {
+            stx: b'{',
+            etx: b'}',
+            sep: b'|',
+        });
+        // the standard control bytes are just data now
+        imgr.write(b"{N|src|title|body\x02}").unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_notification(0, |notification| {
+            assert_eq!(notification.source(), "src");
+            assert_eq!(notification.title(), "title");
+            assert_eq!(notification.body(), "body\x02");
+        });
+    }
+
+    #[test]
+    fn ingress_notification_utf8() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        imgr.write(&notification_frame(&["src", "title \u{1F600}", "body \u{1F600}"])).unwrap();
+
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        system.nm().peek_notification(0, |notification| {
+            assert_eq!(notification.title(), "title \u{1F600}");
+            assert_eq!(notification.body(), "body \u{1F600}");
+        });
+    }
+
+    #[test]
+    fn ingress_notification_invalid_utf8() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let emoji = "\u{1F600}".as_bytes();
+        let truncated = &emoji[..2];
+
+        // in the last section
+        let mut data = vec![STX, b'N', PAYLOAD, b's', PAYLOAD, b't', PAYLOAD];
+        data.extend_from_slice(truncated);
+        data.push(ETX);
+        imgr.write(&data).unwrap();
+        assert_eq!(imgr.process(&mut system), Err(IngressError::InvalidUtf8));
+
+        // in an earlier section
+        let mut data = vec![STX, b'N', PAYLOAD, b's', PAYLOAD];
+        data.extend_from_slice(truncated);
+        data.extend_from_slice(&[PAYLOAD, b'b', ETX]);
+        imgr.write(&data).unwrap();
+        assert_eq!(imgr.process(&mut system), Err(IngressError::InvalidUtf8));
+
+        assert_eq!(system.nm().idx(), 0);
+    }
+
+    #[test]
+    fn escape_into_works() {
+        let mut out = [0u8; 8];
+        assert_eq!(escape_into(&[b'a', STX, ESC], &mut out), Ok(5));
+        assert_eq!(&out[..5], &[b'a', ESC, STX, ESC, ESC]);
+        // not enough room for the escaped data
+        assert_eq!(escape_into(&[ETX; 5], &mut out), Err(IngressError::BufferFull));
+    }
+
+    #[test]
+    fn ingress_small_ring_buffer() {
+        let mut imgr: IngressManager<U8> = IngressManager::new();
+        let capacity: usize = imgr.rb.capacity();
+        assert_eq!(capacity, 8);
+        assert_eq!(imgr.write(&[0u8; 8]), Ok(8));
+        assert_eq!(imgr.write(&[0u8]), Err(IngressError::BufferFull));
+    }
+
+    #[test]
+    fn ingress_small_ring_buffer_syscall() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager<U64> = IngressManager::new();
+        imgr.write(&[STX, b'S', PAYLOAD, b'T', b'0', b'1', b':', b'0', b'2', b':', b'0', b'3', ETX]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        let time = system.rtc().get_time();
+        assert_eq!((time.hours, time.minutes, time.seconds), (1, 2, 3));
+    }
+
+    #[test]
+    fn high_watermark() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager<U16> = IngressManager::new();
+        assert_eq!(imgr.free_space(), 16);
+        assert_eq!(imgr.write(&[0u8; 11]), Ok(11));
+        assert_eq!(imgr.free_space(), 5);
+        assert!(!imgr.is_nearly_full());
+        assert_eq!(imgr.write(&[0u8]), Ok(1));
+        assert!(imgr.is_nearly_full());
+
+        // draining the buffer clears the predicate
+        imgr.process(&mut system).unwrap();
+        assert_eq!(imgr.free_space(), 16);
+        assert!(!imgr.is_nearly_full());
+
+        imgr.set_high_watermark(4);
+        imgr.write(&[0u8; 4]).unwrap();
+        assert!(imgr.is_nearly_full());
+    }
+
+    #[test]
+    fn capture_unknown_frame() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let frame = [STX, b'Z', PAYLOAD, b'a', b'b', PAYLOAD, ESC, ETX, ETX];
+        imgr.write(&frame).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        // dropped by default
+        assert_eq!(imgr.last_unknown_frame(), None);
+
+        imgr.set_capture_unknown(true);
+        imgr.write(&frame).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Unknown)));
+        assert_eq!(imgr.last_unknown_frame(), Some(&[b'Z', PAYLOAD, b'a', b'b', PAYLOAD, ETX][..]));
+        assert_eq!(imgr.stats().unknown_type, 2);
+
+        // kept until the next unknown frame
+        imgr.write(&[STX, b'S', PAYLOAD, b't', ETX]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        assert_eq!(imgr.last_unknown_frame(), Some(&[b'Z', PAYLOAD, b'a', b'b', PAYLOAD, ETX][..]));
+    }
+
+    #[test]
+    fn ingress_syscall() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let mut data = vec![STX, b'S', PAYLOAD];
+        for byte in "T12:21:11".bytes() {
+            data.push(byte);
+        }
+        data.push(ETX);
+        imgr.write(&data).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+
+        assert_eq!(imgr.state(), IngressState::Wait);
+        let time = system.rtc().get_time();
+        assert_eq!((time.hours, time.minutes, time.seconds), (12, 21, 11));
+    }
+
+    #[test]
+    fn ingress_happy_paths() {
+        use crate::ingress::encode::{encode_application, encode_notification, encode_syscall};
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let mut out = [0u8; 64];
+
+        let len = encode_syscall("D2/03/04/2019", &mut out).unwrap();
+        imgr.write(&out[..len]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Syscall)));
+        assert_eq!(imgr.state(), IngressState::Wait);
+        let date = system.rtc().get_date();
+        assert_eq!((date.day, date.date, date.month, date.year), (2, 3, 4, 2019));
+
+        let len = encode_notification("src", "title", "body", &mut out).unwrap();
+        imgr.write(&out[..len]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Notification)));
+        assert_eq!(imgr.state(), IngressState::Wait);
+        assert_eq!(system.nm().idx(), 1);
+
+        let image = [0xAA; 16];
+        let len = encode_application(checksum_ieee(&image), &image, &mut out).unwrap();
+        imgr.write(&out[..len]).unwrap();
+        assert_eq!(imgr.process(&mut system), Ok(Some(Type::Application)));
+        assert_eq!(imgr.state(), IngressState::Wait);
+        assert!(system.am().status().is_loaded);
+    }
+
+    #[test]
+    fn ingress_state_is_observable() {
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        assert_eq!(imgr.state(), IngressState::Wait);
+        imgr.write(&[STX, b'N', PAYLOAD, b's']).unwrap();
+        imgr.process(&mut system).unwrap();
+        assert_eq!(imgr.state(), IngressState::NotificationSource);
+    }
+
+    #[test]
+    fn ingress_write_overflow() {
+        let mut imgr: IngressManager = IngressManager::new();
+        let capacity: usize = imgr.rb.capacity();
+        let data = vec![0u8; capacity + 10];
+        // only the bytes that fit are accepted
+        assert_eq!(imgr.write(&data), Ok(capacity));
+        // once full, no more bytes can be written
+        assert_eq!(imgr.write(&[STX]), Err(IngressError::BufferFull));
+    }
+}
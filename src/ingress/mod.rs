@@ -1,3 +1,5 @@
 pub mod buffer;
+pub mod encode;
 pub mod ingress_manager;
+pub mod source;
 
@@ -0,0 +1,387 @@
+//! Ingress sources
+//!
+//! Abstracts the ingress manager over the communication medium, allowing more than one medium to
+//! feed the same system. Each source has its own `IngressManager`, so two sources can never
+//! interleave bytes into the same half-parsed packet.
+
+use crate::ingress::ingress_manager::IngressManager;
+use crate::system::system::System;
+use embedded_hal::serial::{Read, Write};
+use heapless::consts::*;
+use heapless::spsc::Queue;
+use heapless::ArrayLength;
+
+/// A communication medium that bytes can be received from, and sent back over, into an ingress manager
+/// with a ring buffer of `N` bytes
+pub trait IngressSource<N = U512>
+where
+    N: ArrayLength<u8>,
+{
+    /// Move any received bytes into the ingress manager, leaving the rest with the medium once it is full
+    fn poll(&mut self, mgr: &mut IngressManager<N>);
+    /// Send the ingress manager's pending outbound data, i.e syscall responses, back over the medium
+    fn flush(&mut self, mgr: &mut IngressManager<N>);
+}
+
+/// A BLE characteristic, the values written to the characteristic by the BLE stack are queued until polled.
+/// Outbound data is queued until the BLE stack reads it, i.e to notify the characteristic
+pub struct BleSource {
+    rx: Queue<u8, U128>,
+    tx: Queue<u8, U128>,
+}
+
+impl BleSource {
+    /// Creates a new, empty, BLE source
+    pub fn new() -> Self {
+        Self {
+            rx: Queue::new(),
+            tx: Queue::new(),
+        }
+    }
+
+    /// Called by the BLE stack to take outbound data into `data`, returning the number of bytes taken
+    pub fn read(&mut self, data: &mut [u8]) -> usize {
+        let mut idx = 0;
+        while idx < data.len() {
+            match self.tx.dequeue() {
+                Some(byte) => data[idx] = byte,
+                None => break,
+            }
+            idx += 1;
+        }
+        idx
+    }
+
+    /// Called by the BLE stack when a value is written to the characteristic, returning the number of bytes
+    /// queued. Fewer than `value.len()` are queued once the queue is full, the rest should be written again
+    /// once the source has been polled
+    pub fn on_write(&mut self, value: &[u8]) -> usize {
+        for (idx, byte) in value.iter().enumerate() {
+            if self.rx.enqueue(*byte).is_err() {
+                warn!("BLE source full, {} bytes not queued", value.len() - idx);
+                return idx;
+            }
+        }
+        value.len()
+    }
+}
+
+impl<N> IngressSource<N> for BleSource
+where
+    N: ArrayLength<u8>,
+{
+    fn poll(&mut self, mgr: &mut IngressManager<N>) {
+        // the rest stay queued until the ingress manager has been processed
+        while mgr.free_space() > 0 {
+            match self.rx.dequeue() {
+                Some(byte) => {
+                    let _ = mgr.write(&[byte]);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn flush(&mut self, mgr: &mut IngressManager<N>) {
+        let mut data = [0u8; 128];
+        let room = self.tx.capacity() - self.tx.len();
+        let len = mgr.read(&mut data[..room]);
+        for byte in &data[..len] {
+            let _ = self.tx.enqueue(*byte);
+        }
+    }
+}
+
+/// A serial endpoint, i.e a USB serial port
+pub struct SerialSource<S> {
+    serial: S,
+}
+
+impl<S> SerialSource<S>
+where
+    S: Read<u8> + Write<u8>,
+{
+    /// Creates a new serial source
+    pub fn new(serial: S) -> Self {
+        Self {
+            serial,
+        }
+    }
+
+    /// Release the underlying serial endpoint
+    pub fn release(self) -> S {
+        self.serial
+    }
+}
+
+impl<S, N> IngressSource<N> for SerialSource<S>
+where
+    S: Read<u8> + Write<u8>,
+    N: ArrayLength<u8>,
+{
+    fn poll(&mut self, mgr: &mut IngressManager<N>) {
+        // read until the endpoint would block, or the ingress manager is full
+        while mgr.free_space() > 0 {
+            match self.serial.read() {
+                Ok(byte) => {
+                    let _ = mgr.write(&[byte]);
+                }
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn flush(&mut self, mgr: &mut IngressManager<N>) {
+        mgr.flush(&mut self.serial);
+    }
+}
+
+/// A source paired with the ingress manager that parses its bytes, with a ring buffer of `N` bytes
+pub struct Channel<'a, N = U512>
+where
+    N: ArrayLength<u8>,
+{
+    source: &'a mut dyn IngressSource<N>,
+    mgr: IngressManager<N>,
+}
+
+impl<'a, N> Channel<'a, N>
+where
+    N: ArrayLength<u8>,
+{
+    /// Creates a new channel for the source
+    pub fn new(source: &'a mut dyn IngressSource<N>) -> Self {
+        Self {
+            source,
+            mgr: IngressManager::new(),
+        }
+    }
+
+    /// The ingress manager of this channel
+    pub fn mgr(&mut self) -> &mut IngressManager<N> {
+        &mut self.mgr
+    }
+}
+
+/// Round robins many channels into a single system
+pub struct Multiplexer<'a, N = U512>
+where
+    N: ArrayLength<u8>,
+{
+    channels: &'a mut [Channel<'a, N>],
+    next: usize,
+}
+
+impl<'a, N> Multiplexer<'a, N>
+where
+    N: ArrayLength<u8>,
+{
+    /// Creates a new multiplexer over the channels
+    pub fn new(channels: &'a mut [Channel<'a, N>]) -> Self {
+        Self {
+            channels,
+            next: 0,
+        }
+    }
+
+    /// Poll and process every channel at `now_ms`, starting with a different channel each time so no source is
+    /// starved. Each ingress manager is ticked first, dropping a partial packet its source has stopped sending.
+    /// Any responses are sent back over the channel the syscall arrived on
+    pub fn process(&mut self, system: &mut System, now_ms: u32) {
+        let count = self.channels.len();
+        for offset in 0..count {
+            let channel = &mut self.channels[(self.next + offset) % count];
+            channel.mgr.tick(now_ms);
+            channel.source.poll(&mut channel.mgr);
+            if let Err(err) = channel.mgr.process(system) {
+                error!("Failed to process ingress data {:?}", err);
+            }
+            channel.source.flush(&mut channel.mgr);
+        }
+        if count > 0 {
+            self.next = (self.next + 1) % count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::vec::Vec;
+    use crate::ingress::buffer::Type;
+
+    /// Feeds a few bytes of its packet per poll, keeping everything sent back
+    struct MockSource {
+        data: Vec<u8>,
+        idx: usize,
+        sent: Vec<u8>,
+    }
+
+    impl IngressSource for MockSource {
+        fn poll(&mut self, mgr: &mut IngressManager) {
+            let end = core::cmp::min(self.idx + 3, self.data.len());
+            mgr.write(&self.data[self.idx..end]).unwrap();
+            self.idx = end;
+        }
+
+        fn flush(&mut self, mgr: &mut IngressManager) {
+            let mut data = [0u8; 16];
+            let len = mgr.read(&mut data);
+            self.sent.extend_from_slice(&data[..len]);
+        }
+    }
+
+    fn notification(title: &str) -> MockSource {
+        let mut data = vec![2, b'N', 31];
+        data.extend_from_slice(b"src");
+        data.push(31);
+        data.extend_from_slice(title.as_bytes());
+        data.push(31);
+        data.extend_from_slice(b"body");
+        data.push(3);
+        MockSource { data, idx: 0, sent: Vec::new() }
+    }
+
+    fn syscall(payload: &str) -> MockSource {
+        let mut data = vec![2, b'S', 31];
+        data.extend_from_slice(payload.as_bytes());
+        data.push(3);
+        MockSource { data, idx: 0, sent: Vec::new() }
+    }
+
+    #[test]
+    fn multiplexer_does_not_interleave() {
+        let mut system = System::mock();
+        let mut first = notification("first");
+        let mut second = notification("second");
+        let mut channels = [Channel::new(&mut first), Channel::new(&mut second)];
+        let mut mux = Multiplexer::new(&mut channels);
+        for _ in 0..10 {
+            mux.process(&mut system, 0);
+        }
+
+        assert_eq!(system.nm().idx(), 2);
+        let mut titles = Vec::new();
+        for idx in 0..2 {
+            system.nm().peek_notification(idx, |notification| {
+                titles.push(String::from(notification.title()));
+            });
+        }
+        titles.sort();
+        assert_eq!(titles, ["first", "second"]);
+    }
+
+    #[test]
+    fn multiplexer_responds_on_the_same_channel() {
+        let mut system = System::mock();
+        let mut first = notification("first");
+        let mut second = syscall("DN7");
+        {
+            let mut channels = [Channel::new(&mut first), Channel::new(&mut second)];
+            let mut mux = Multiplexer::new(&mut channels);
+            for _ in 0..10 {
+                mux.process(&mut system, 0);
+            }
+        }
+        // the id of the stored notification, and the failed dismissal
+        assert_eq!(first.sent, b"\x02R\x1fI0\x03");
+        assert_eq!(second.sent, b"\x02R\x1fEOutOfRange\x03");
+    }
+
+    /// Feeds one slice per poll, keeping everything sent back
+    struct StallingSource {
+        polls: Vec<&'static [u8]>,
+        sent: Vec<u8>,
+    }
+
+    impl IngressSource for StallingSource {
+        fn poll(&mut self, mgr: &mut IngressManager) {
+            if !self.polls.is_empty() {
+                mgr.write(self.polls.remove(0)).unwrap();
+            }
+        }
+
+        fn flush(&mut self, mgr: &mut IngressManager) {
+            let mut data = [0u8; 16];
+            let len = mgr.read(&mut data);
+            self.sent.extend_from_slice(&data[..len]);
+        }
+    }
+
+    /// Process a syscall that stalls part way through, resuming at `resume_ms`, returning what was sent back
+    fn stalled_syscall(resume_ms: u32) -> Vec<u8> {
+        let mut system = System::mock();
+        let mut source = StallingSource { polls: vec![b"\x02S\x1fDN", b"7\x03"], sent: Vec::new() };
+        {
+            let mut channels = [Channel::new(&mut source)];
+            let mut mux = Multiplexer::new(&mut channels);
+            mux.process(&mut system, 0);
+            mux.process(&mut system, resume_ms);
+        }
+        source.sent
+    }
+
+    #[test]
+    fn multiplexer_times_out_partial_packets() {
+        use crate::ingress::ingress_manager::PARTIAL_TIMEOUT_MS;
+        assert_eq!(stalled_syscall(PARTIAL_TIMEOUT_MS), b"\x02R\x1fEOutOfRange\x03");
+        // the rest of the packet arrives too late, and is ignored
+        assert_eq!(stalled_syscall(PARTIAL_TIMEOUT_MS + 1), b"");
+    }
+
+    #[test]
+    fn ble_source_backpressure() {
+        let mut ble = BleSource::new();
+        assert_eq!(ble.on_write(&[0u8; 100]), 100);
+        // only the bytes that fit are queued
+        assert_eq!(ble.on_write(&[0u8; 100]), 28);
+        assert_eq!(ble.on_write(&[0u8; 1]), 0);
+
+        let mut mgr: IngressManager = IngressManager::new();
+        ble.poll(&mut mgr);
+        assert_eq!(ble.on_write(&[0u8; 100]), 100);
+    }
+
+    #[test]
+    fn smaller_ring_buffer() {
+        let mut system = System::mock();
+        let mut ble = BleSource::new();
+        assert_eq!(ble.on_write(&[2, b'S', 31, b'V', 3]), 5);
+        {
+            let mut channels: [Channel<U16>; 1] = [Channel::new(&mut ble)];
+            let mut mux = Multiplexer::new(&mut channels);
+            mux.process(&mut system, 0);
+        }
+        // the version syscall was answered
+        let mut data = [0u8; 32];
+        let len = ble.read(&mut data);
+        assert_eq!(&data[..4], &[2, b'R', 31, b'V']);
+        assert_eq!(data[len - 1], 3);
+    }
+
+    #[test]
+    fn ble_source_stops_when_full() {
+        let mut system = System::mock();
+        let mut mgr: IngressManager = IngressManager::new();
+        let space = mgr.free_space();
+        // bytes outside of a frame, ignored once processed
+        mgr.write(&vec![0u8; space - 2]).unwrap();
+
+        let mut ble = BleSource::new();
+        ble.on_write(&[2, b'S', 31, b'V', 3]);
+        ble.poll(&mut mgr);
+        assert_eq!(mgr.free_space(), 0);
+        assert_eq!(mgr.stats().overflow_drops, 0);
+
+        // the rest are kept until there is room
+        mgr.process(&mut system).unwrap();
+        ble.poll(&mut mgr);
+        assert_eq!(mgr.process(&mut system), Ok(Some(Type::Syscall)));
+
+        ble.flush(&mut mgr);
+        let mut data = [0u8; 32];
+        let len = ble.read(&mut data);
+        assert_eq!(&data[..3], &[2, b'R', 31]);
+        assert_eq!(data[len - 1], 3);
+    }
+}
@@ -8,9 +8,17 @@
 #[macro_use]
 extern crate std;
 
+#[cfg(not(feature = "defmt"))]
 #[macro_use]
 extern crate log;
 
+#[cfg(feature = "defmt")]
+#[macro_use]
+mod defmt_log;
+
+#[cfg(feature = "serde")]
+extern crate alloc;
+
 pub mod application;
 pub mod ingress;
 pub mod system;
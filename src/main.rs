@@ -22,6 +22,7 @@ use crate::hal::{
     datetime::Date,
     delay::Delay,
     dma::{dma1, CircBuffer, Event},
+    gpio::{gpioa::PA1, Output, PushPull},
     i2c::I2c,
     prelude::*,
     rtc::Rtc,
@@ -48,23 +49,26 @@ use cortex_m_log::{
 
 use cortex_m_rt::{exception, ExceptionFrame};
 use rtfm::app;
-use cortex_m::{peripheral::DWT, asm};
+use cortex_m::{peripheral::{DWT, SCB}, asm};
 use hm11::{command::Command, Hm11};
 use max17048::Max17048;
 
-use crate::ingress::ingress_manager::IngressManager;
+use crate::ingress::ingress_manager::DefaultIngressManager;
 use crate::application::{
     application_manager::{ApplicationManager, Ram},
     display_manager::DisplayManager
 };
 
 use crate::system::{ 
-    input::{InputManager, TscManager},
+    input::{HalTouchSense, InputManager, Polarity, TscManager, HYSTERESIS, IDLE_AFTER_MS, IDLE_POLL_MS, LONG_PRESS_MS},
     bms::BatteryManagement,
     system::{
         System,
+        Backlight,
+        ResetTarget,
         CPU_USAGE_POLL_HZ,
         TSC_HZ,
+        INPUT_DEBOUNCE_SAMPLES,
         SYSTICK_HZ,
         DMA_HALF_BYTES,
         SPI_MHZ,
@@ -72,7 +76,9 @@ use crate::system::{
         SYS_CLK_HZ,
     },
     notification::NotificationManager,
+    haptic::Motor,
 };
+use embedded_hal::digital::v2::OutputPin;
 
 
 #[cfg(feature = "itm")]
@@ -84,10 +90,11 @@ const LOG_LEVEL: log::LevelFilter = log::LevelFilter::Off;
 const APP: () = {
     struct Resources {
         CB: CircBuffer<&'static mut [[u8; DMA_HALF_BYTES]; 2], dma1::C6>,
-        IMNG: IngressManager,
+        IMNG: DefaultIngressManager,
         INPUT_MGR: InputManager,
         DMNG: DisplayManager,
         USART2_RX: hal::serial::Rx<hal::stm32l4::stm32l4x2::USART2>,
+        USART2_TX: hal::serial::Tx<hal::stm32l4::stm32l4x2::USART2>,
         DISPLAY: Ssd1351,
         BT_CONN: BluetoothConnectedPin,
         SYSTEM: System,
@@ -103,6 +110,10 @@ const APP: () = {
         #[init(0)]
         IDLE_COUNT: u32,
         #[init(0)]
+        UPTIME_MS: u32,
+        #[init(0)]
+        UPTIME_TICKS: u32,
+        #[init(0)]
         LAST_BATT_PERCENT: u16,
         #[init(None)]
         LOGGER: Option<LoggerType>,
@@ -213,7 +224,7 @@ const APP: () = {
         delay.delay_ms(100_u8); // allow module to reset
         hm11.send_with_delay(Command::Test, &mut delay)
             .expect("HM11 - Module did not responde after reboot");
-        let (_, rx) = hm11.release();
+        let (tx, rx) = hm11.release();
 
         channels.6.listen(Event::HalfTransfer);
         channels.6.listen(Event::TransferComplete);
@@ -227,7 +238,7 @@ const APP: () = {
             gpiob
                 .pb5
                 .into_touch_channel(&mut gpiob.moder, &mut gpiob.otyper, &mut gpiob.afrl);
-        let mut middle_button =
+        let middle_button =
             gpiob
                 .pb6
                 .into_touch_channel(&mut gpiob.moder, &mut gpiob.otyper, &mut gpiob.afrl);
@@ -244,25 +255,7 @@ const APP: () = {
         };
         let tsc = Tsc::tsc(cx.device.TSC, sample_pin, &mut rcc.ahb1, Some(tsc_config));
 
-        #[cfg(feature = "dyn-tsc-cal")]
-        let tsc_threshold =  {
-            const TSC_SAMPLES: u16 = 12;
-            // Acquire for rough estimate of capacitance
-            let mut baseline = 0;
-            for _ in 0..TSC_SAMPLES {
-                baseline += tsc.acquire(&mut middle_button).unwrap_or_else(|err|{
-                    panic!("Failed to calibrate tsc {:?}", err);
-                });
-                delay.delay_ms(20u8);
-            }
-
-            ((baseline / TSC_SAMPLES) / 100) * 98
-        };
-
-        #[cfg(not(feature = "dyn-tsc-cal"))]
-        let tsc_threshold = {
-            1060 // acquired through testing
-        };
+        let tsc_threshold = 1060; // acquired through testing
         
 
         /* T4056 input pins */
@@ -276,6 +269,11 @@ const APP: () = {
             .pa8
             .into_floating_input(&mut gpioa.moder, &mut gpioa.pupdr);
 
+        /* Vibration motor, switched through a transistor */
+        let motor = gpioa
+            .pa1
+            .into_push_pull_output(&mut gpioa.moder, &mut gpioa.otyper);
+
         /* Fuel Guage */
         let mut scl = gpioa
             .pa9
@@ -292,7 +290,7 @@ const APP: () = {
         let i2c = I2c::i2c1(cx.device.I2C1, (scl, sda), I2C_KHZ.khz(), clocks, &mut rcc.apb1r1);
         let max17048 = Max17048::new(i2c);
         let bms = BatteryManagement::new(max17048, chrg, stdby);
-        let imgr = IngressManager::new();
+        let imgr = DefaultIngressManager::new();
         let nmgr = NotificationManager::new();
 
         /* Give the application manager its ram */
@@ -310,24 +308,33 @@ const APP: () = {
         );
         cpu.listen(TimerEvent::TimeOut);
 
+        /* TIM6 also keeps the uptime, so it runs even when the inputs are disabled */
         let mut input = Timer::tim6(cx.device.TIM6, TSC_HZ.hz(), clocks, &mut rcc.apb1r1);
-        #[cfg(not(feature = "disable-input"))]
-        {
-            input.listen(TimerEvent::TimeOut);
-        }
+        input.listen(TimerEvent::TimeOut);
 
         let buffer: &'static mut [[u8; crate::DMA_HALF_BYTES]; 2] = cx.resources.DMA_BUFFER;
-        let tsc_mgr = TscManager::new(tsc, tsc_threshold, left_button, middle_button, right_button);
-        let input_mgr = InputManager::new(tsc_mgr);
+        #[allow(unused_mut)]
+        let mut tsc_mgr = TscManager::new(HalTouchSense::new(tsc, left_button, middle_button, right_button), tsc_threshold, HYSTERESIS);
+        #[cfg(feature = "dyn-tsc-cal")]
+        {
+            tsc_mgr.calibrate(tsc_mgr.samples()).unwrap_or_else(|err|{
+                panic!("Failed to calibrate tsc {:?}", err);
+            });
+        }
+        let mut input_mgr = InputManager::new(tsc_mgr, INPUT_DEBOUNCE_SAMPLES, Polarity::ActiveLow, LONG_PRESS_MS);
+        input_mgr.set_duty_cycle(Some((IDLE_AFTER_MS, IDLE_POLL_MS)));
         let dmng = DisplayManager::default();
-        let mut system = System::new(rtc, bms, nmgr, amgr);
-        system.ss().tsc_threshold = input_mgr.threshold();
+        let mut system = System::new(cortex_m::singleton!(: Rtc = rtc).unwrap(), bms, nmgr, amgr);
+        system.set_reset_target(cortex_m::singleton!(: ScbReset = ScbReset(cx.core.SCB)).unwrap());
+        system.haptic().set_motor(cortex_m::singleton!(: GpioMotor = GpioMotor(motor)).unwrap());
+        system.ss().tsc_threshold = input_mgr.thresholds();
         // rtfm::pend(crate::hal::interrupt::TIM2); // make sure systick runs first
 
         // Resources that need to be initialized are passed back here
         init::LateResources {
             CB: rx.circ_read(channels.6, buffer),
             USART2_RX: rx,
+            USART2_TX: tx,
             IMNG: imgr,
             DISPLAY: display,
             SYSTEM: system,
@@ -360,38 +367,90 @@ const APP: () = {
 
     /// The main thread of the watch, this is called `SYSTICK_HZ` times a second, to perform 
     /// housekeeping operations
-    #[task(binds = TIM2, resources = [IMNG, SYSTEM, SYSTICK, IDLE_COUNT], spawn = [display_manager])]
+    #[task(binds = TIM2, resources = [IMNG, SYSTEM, SYSTICK, IDLE_COUNT, UPTIME_MS, USART2_TX, INPUT_MGR], spawn = [display_manager, input_handler])]
     fn systemtick(cx: systemtick::Context) {
         let mut system = cx.resources.SYSTEM;
         let mut mgr = cx.resources.IMNG;
         let mut idle = cx.resources.IDLE_COUNT;
+        let mut input_mgr = cx.resources.INPUT_MGR;
+        let uptime = cx.resources.UPTIME_MS.lock(|uptime| *uptime);
+        let tx = cx.resources.USART2_TX;
+        let spawn = cx.spawn;
 
-        cx.spawn.display_manager().unwrap_or_else(|_err| {
+        spawn.display_manager().unwrap_or_else(|_err| {
             error!("Failed to spawn display manager");
         });
         
         system.lock(|system|{
-            system.bms().process();
+            let events = mgr.lock(|m| input_mgr.lock(|im| system.tick(uptime, m, im)));
+            if let Some(err) = events.notify {
+                error!("Failed to notify low battery {:?}", err);
+            }
+            if let Some(err) = events.input {
+                warn!("Input manager recovered from {:?}", err);
+            }
+            while let Some(event) = system.bms().event() {
+                idle.lock(|val| *val = 0); // wake the display
+                if let Err(event) = spawn.input_handler(event) {
+                    error!("Failed to spawn input task. Input {:?}", event);
+                }
+            }
+            if system.poll_alarm() {
+                info!("Alarm fired");
+                idle.lock(|val| *val = 0); // wake the display
+            }
             system.ss().idle_count = idle.lock(|val| {
                 let value = *val;
                 *val += 1; // append to idle count
                 value
             });
+            system.poll_watchdog(uptime).unwrap_or_else(|err| {
+                error!("Failed to notify application watchdog {:?}", err);
+            });
             mgr.lock(|m| {
-                m.process(system);
+                if let Some(response) = m.process_with_ack(system) {
+                    m.queue_response(&response).unwrap_or_else(|err| {
+                        error!("Failed to queue the ingress response {:?}", err);
+                    });
+                }
+                m.flush(tx); // send any syscall responses and the ACK or NACK
             });
+            if let Some(threshold) = system.take_tsc_threshold() {
+                match input_mgr.lock(|im| im.set_threshold(threshold).map(|_| im.thresholds())) {
+                    Ok(thresholds) => system.ss().tsc_threshold = thresholds,
+                    Err(err) => error!("Failed to set the touch threshold {:?}", err),
+                }
+            }
+            if system.take_tsc_calibration() {
+                // sampled by the acquisition interrupts, blocking here would hold off the uptime timer
+                if let Err(err) = input_mgr.lock(|im| im.start_calibration(im.samples())) {
+                    error!("Failed to start the touch calibration {:?}", err);
+                }
+            }
+            match input_mgr.lock(|im| im.take_calibration()) {
+                Some(Ok(thresholds)) => system.ss().tsc_threshold = thresholds,
+                Some(Err(err)) => error!("Failed to calibrate the touch thresholds {:?}", err),
+                None => {}
+            }
         });
         cx.resources.SYSTICK.wait().expect("systick timer was already cleared"); // this should never panic as if we are in the IT the uif bit is set
     }
 
-    /// Hardware timer, initiates tsc aquisitions
-    #[task(binds = TIM6_DACUNDER, resources = [INPUT_MGR, TIM6], priority = 3)] // TIM6
+    /// Hardware timer, keeps the uptime and initiates tsc aquisitions
+    #[task(binds = TIM6_DACUNDER, resources = [INPUT_MGR, TIM6, UPTIME_MS, UPTIME_TICKS], priority = 3)] // TIM6
     fn tsc_initiator(cx: tsc_initiator::Context) {
-        match cx.resources.INPUT_MGR.start_new() {
-            Ok(_) => {},
-            Err(e) => {
-                if e != system::input::Error::AcquisitionInProgress {
-                    panic!("{:?}", e);
+        // counted in ticks so the uptime does not drift, TSC_HZ does not divide a second evenly
+        *cx.resources.UPTIME_TICKS = cx.resources.UPTIME_TICKS.wrapping_add(1);
+        *cx.resources.UPTIME_MS = (u64::from(*cx.resources.UPTIME_TICKS) * 1000 / u64::from(TSC_HZ)) as u32;
+        #[cfg(not(feature = "disable-input"))]
+        {
+            cx.resources.INPUT_MGR.tick(*cx.resources.UPTIME_MS);
+            match cx.resources.INPUT_MGR.start_new() {
+                Ok(_) => {},
+                Err(e) => {
+                    if e != system::input::Error::AcquisitionInProgress {
+                        panic!("{:?}", e);
+                    }
                 }
             }
         }
@@ -444,27 +503,22 @@ const APP: () = {
         let input_mgr = cx.resources.INPUT_MGR;
         match input_mgr.process_result() {
             Ok(_) => {
-                match input_mgr.output() {
-                    Ok(input) => {
-                        *cx.resources.IDLE_COUNT = 0; // we are no longer idle
-                        info!("Output => {:?}", input);
-                        match cx.spawn.input_handler(input) {
-                            Ok(_) => {},
-                            Err(e) => panic!("Failed to spawn input task. Input {:?}", e)
-                        }
-                    },
-                    Err(e) => {
-                        if e != system::input::Error::NoInput {
-                            error!("Input Error, {:?}", e);
-                        }
+                while let Ok(input) = input_mgr.output() {
+                    *cx.resources.IDLE_COUNT = 0; // we are no longer idle
+                    info!("Output => {:?}", input);
+                    if let Err(input) = cx.spawn.input_handler(input) {
+                        error!("Failed to spawn input task. Input {:?}", input);
                     }
                 }
             },
-            Err(e) => {
-                if e != system::input::Error::Incomplete {
-                    panic!("process_result error: {:?}", e)
-                }
+            Err(system::input::Error::Incomplete) => {},
+            Err(system::input::Error::AcquisitionFailed(pin)) => {
+                warn!("TSC acquisition failed on pin {}", pin);
+            }
+            Err(system::input::Error::InvalidInputVector(vector)) => {
+                error!("Input Error, invalid vector {}", vector);
             }
+            Err(e) => panic!("process_result error: {:?}", e)
         }
 
         
@@ -483,7 +537,13 @@ const APP: () = {
                 .partial_peek(|buf, _half| {
                     let len = buf.len();
                     if len > 0 {
-                        mgr.write(buf);
+                        let written = mgr.write(buf).unwrap_or_else(|err| {
+                            error!("Failed to write into the ingress manager {:?}", err);
+                            0
+                        });
+                        if written < len {
+                            warn!("Ingress manager dropped {} bytes", len - written);
+                        }
                     }
                     Ok((len, ()))
                 }).unwrap_or_else(|err|{
@@ -500,7 +560,13 @@ const APP: () = {
         cx.resources
             .CB
             .peek(|buf, _half| {
-                mgr.write(buf);
+                let written = mgr.write(buf).unwrap_or_else(|err| {
+                    error!("Failed to write into the ingress manager {:?}", err);
+                    0
+                });
+                if written < buf.len() {
+                    warn!("Ingress manager dropped {} bytes", buf.len() - written);
+                }
             }).unwrap_or_else(|err|{
                 error!("Failed to full peek into circular buffer {:?}", err);
             });
@@ -518,6 +584,7 @@ const APP: () = {
         let mut sys = cx.resources.SYSTEM;
         // let mut system = cx.resources.SYSTEM;
         dmngr.lock(|dmng|{
+            sys.lock(|system| system.apply_brightness(&mut DisplayBacklight(&mut display)));
             #[cfg(feature = "crc-fb")]
             {
                 let is_idle = sys.lock(|system| system.is_idle());
@@ -568,6 +635,42 @@ const APP: () = {
     }
 };
 
+/// The brightest master contrast current of the SSD1351
+const MAX_CONTRAST: u8 = 15;
+
+/// Sets the brightness of the SSD1351 through its master contrast current, it has no backlight
+pub struct DisplayBacklight<'a>(&'a mut Ssd1351);
+
+impl<'a> Backlight for DisplayBacklight<'a> {
+    fn set_brightness(&mut self, percent: u8) {
+        let contrast = (u16::from(percent) * u16::from(MAX_CONTRAST) / 100) as u8;
+        if self.0.set_contrast(contrast).is_err() {
+            error!("Failed to set the display contrast to {}", contrast);
+        }
+    }
+}
+
+/// Drives the vibration motor from a push-pull output
+pub struct GpioMotor(PA1<Output<PushPull>>);
+
+impl Motor for GpioMotor {
+    fn set(&mut self, on: bool) {
+        let result = if on { self.0.set_high() } else { self.0.set_low() };
+        if result.is_err() {
+            error!("Failed to switch the vibration motor {}", on);
+        }
+    }
+}
+
+/// Resets the system through the system control block
+pub struct ScbReset(SCB);
+
+impl ResetTarget for ScbReset {
+    fn reset(&mut self) {
+        self.0.system_reset();
+    }
+}
+
 #[exception]
 fn HardFault(ef: &ExceptionFrame) -> ! {
     panic!("{:#?}", ef);
@@ -0,0 +1,119 @@
+//! Alarm
+//!
+//! A one shot alarm, checked against the real time clock by the system tick
+
+use crate::types::hal::datetime::Time;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+#[derive(Debug, Default)]
+pub struct Alarm {
+    /// when the alarm is due, in seconds since midnight
+    at: Option<u32>,
+    /// the time of the previous poll, in seconds since midnight
+    last: Option<u32>,
+    fired: bool,
+}
+
+impl Alarm {
+
+    /// Set the alarm to fire the next time the clock reaches `at`, replacing any previous alarm
+    pub fn set(&mut self, at: &Time) {
+        self.at = Some(Alarm::seconds(at));
+        self.fired = false;
+    }
+
+    /// Cancel the alarm, and dismiss it if it has fired
+    pub fn clear(&mut self) {
+        self.at = None;
+        self.fired = false;
+    }
+
+    /// The time the alarm is due, if one is set
+    pub fn at(&self) -> Option<Time> {
+        use crate::types::hal::prelude::*;
+        self.at.map(|at| Time::new((at / 3600).hours(), (at / 60 % 60).minutes(), (at % 60).seconds(), false))
+    }
+
+    /// Whether the alarm has fired and not yet been dismissed with `clear`
+    pub fn fired(&self) -> bool {
+        self.fired
+    }
+
+    /// Check the alarm against the current time, returns true when the alarm fires.
+    ///
+    /// The alarm fires once the clock passes the alarm time, including across midnight
+    pub fn poll(&mut self, now: &Time) -> bool {
+        let now = Alarm::seconds(now) % SECONDS_PER_DAY;
+        let last = self.last.replace(now);
+        let (at, last) = match (self.at, last) {
+            (Some(at), Some(last)) => (at, last),
+            _ => return false, // nothing to pass yet
+        };
+        let passed = if last <= now {
+            last < at && at <= now
+        } else { // the clock wrapped at midnight
+            last < at || at <= now
+        };
+        if passed {
+            self.at = None;
+            self.fired = true;
+        }
+        passed
+    }
+
+    fn seconds(time: &Time) -> u32 {
+        time.hours * 3600 + time.minutes * 60 + time.seconds
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::hal::prelude::*;
+
+    fn time(hours: u32, minutes: u32, seconds: u32) -> Time {
+        Time::new(hours.hours(), minutes.minutes(), seconds.seconds(), false)
+    }
+
+    #[test]
+    fn alarm_fires_once() {
+        let mut alarm = Alarm::default();
+        alarm.set(&time(7, 30, 0));
+        assert_eq!(alarm.at(), Some(time(7, 30, 0)));
+        assert!(!alarm.poll(&time(7, 29, 58)));
+        assert!(!alarm.poll(&time(7, 29, 59)));
+        assert!(!alarm.fired());
+        assert!(alarm.poll(&time(7, 30, 1)));
+        assert!(alarm.fired());
+        assert_eq!(alarm.at(), None);
+        assert!(!alarm.poll(&time(7, 30, 2)));
+        alarm.clear();
+        assert!(!alarm.fired());
+    }
+
+    #[test]
+    fn alarm_fires_across_midnight() {
+        let mut alarm = Alarm::default();
+        alarm.set(&time(0, 0, 0));
+        assert!(!alarm.poll(&time(23, 59, 59)));
+        assert!(alarm.poll(&time(0, 0, 1)));
+
+        // an alarm set in the past fires tomorrow
+        assert!(!alarm.poll(&time(12, 0, 0)));
+        alarm.set(&time(6, 0, 0));
+        assert!(!alarm.poll(&time(12, 0, 1)));
+        assert!(!alarm.poll(&time(23, 0, 0)));
+        assert!(alarm.poll(&time(6, 0, 0)));
+    }
+
+    #[test]
+    fn cleared_alarm_does_not_fire() {
+        let mut alarm = Alarm::default();
+        alarm.set(&time(7, 30, 0));
+        assert!(!alarm.poll(&time(7, 0, 0)));
+        alarm.clear();
+        assert!(!alarm.poll(&time(8, 0, 0)));
+        assert!(!alarm.fired());
+    }
+}
@@ -3,7 +3,29 @@
 //! 
 
 use crate::types::{BatteryManagementIC, ChargeStatusPin, StandbyStatusPin};
+use crate::types::InputEvent;
 use embedded_hal::digital::v2::*;
+use heapless::consts::*;
+use heapless::spsc::Queue;
+
+/// A typical single cell LiPo discharge curve, (millivolts, percent) in ascending order
+pub const DEFAULT_CURVE: &[(u16, u8)] = &[
+    (3300, 0),
+    (3600, 5),
+    (3700, 20),
+    (3750, 35),
+    (3800, 50),
+    (3850, 60),
+    (3900, 70),
+    (4000, 85),
+    (4100, 95),
+    (4200, 100),
+];
+
+/// The default percentage below which the battery is low
+pub const LOW_BATTERY_PERCENT: u8 = 15;
+/// The percentage above the low threshold the battery must rise to before it can be low again
+pub const LOW_BATTERY_HYSTERESIS: u8 = 5;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum State {
@@ -17,6 +39,12 @@ pub struct BatteryManagement {
     csp: ChargeStatusPin,
     ssp: StandbyStatusPin,
     state: State,
+    /// the cell voltage read by the last `process`, in millivolts
+    mv: u16,
+    curve: &'static [(u16, u8)],
+    low_threshold: u8,
+    low: bool,
+    events: Queue<InputEvent, U4>,
 }
 
 impl BatteryManagement {
@@ -28,6 +56,11 @@ impl BatteryManagement {
             csp,
             ssp,
             state: State::Draining,
+            mv: 0,
+            curve: DEFAULT_CURVE,
+            low_threshold: LOW_BATTERY_PERCENT,
+            low: false,
+            events: Queue::new(),
         }
     }
 
@@ -36,6 +69,16 @@ impl BatteryManagement {
         self.state
     }
 
+    /// Whether the battery is currently charging
+    pub fn is_charging(&self) -> bool {
+        self.state == State::Charging
+    }
+
+    /// The next charging transition, `InputEvent::ChargingStarted` or `InputEvent::ChargingStopped`
+    pub fn event(&mut self) -> Option<InputEvent> {
+        self.events.dequeue()
+    }
+
     /// Returns the current state of charge (%) of the battery
     pub fn soc(&mut self) -> u16 {
         //TODO should we cache this value and instead only update when we process?
@@ -45,15 +88,83 @@ impl BatteryManagement {
         }))
     }
 
+    /// The cell voltage read by the last `process`, in millivolts
+    pub fn voltage(&self) -> u16 {
+        self.mv
+    }
+
+    /// The percentage charge of the battery, estimated from the cell voltage using the discharge curve
+    pub fn percentage(&self) -> u8 {
+        interpolate(self.curve, self.mv)
+    }
+
+    /// Sets the discharge curve used by `percentage`, (millivolts, percent) pairs in ascending order
+    pub fn set_curve(&mut self, curve: &'static [(u16, u8)]) {
+        self.curve = curve;
+    }
+
+    /// Sets the percentage below which the battery is low
+    pub fn set_low_threshold(&mut self, percentage: u8) {
+        self.low_threshold = percentage;
+    }
+
+    /// Whether the battery is low
+    pub fn is_low(&self) -> bool {
+        self.low
+    }
+
+    /// Track the battery `percentage`, returns true only when it falls below the low threshold.
+    ///
+    /// The battery is not low again until it has risen `LOW_BATTERY_HYSTERESIS` above the threshold
+    pub fn update_low(&mut self, percentage: u8) -> bool {
+        if !self.low && percentage < self.low_threshold {
+            self.low = true;
+            return true;
+        }
+        if self.low && percentage >= self.low_threshold.saturating_add(LOW_BATTERY_HYSTERESIS) {
+            self.low = false;
+        }
+        false
+    }
+
     /// internal processing of the bms
     pub fn process(&mut self) {
-        if self.csp.is_low().unwrap() {
-            self.state = State::Charging;
+        match self.bms.vcell() {
+            Ok(volts) => self.mv = (volts * 1000.0) as u16,
+            Err(err) => error!("Failed to read vcell from bms: {:?}", err),
+        }
+        let state = if self.csp.is_low().unwrap() {
+            State::Charging
         } else if self.ssp.is_high().unwrap() {
-            self.state = State::Draining;
+            State::Draining
         } else {
-            self.state = State::Charged;
+            State::Charged
+        };
+        self.update_state(state);
+    }
+
+    /// Update the state, queuing an event if charging started or stopped
+    fn update_state(&mut self, state: State) {
+        let event = match (self.state == State::Charging, state == State::Charging) {
+            (false, true) => Some(InputEvent::ChargingStarted),
+            (true, false) => Some(InputEvent::ChargingStopped),
+            _ => None,
+        };
+        if let Some(event) = event {
+            info!("Battery {:?}", event);
+            if self.events.enqueue(event).is_err() {
+                warn!("Dropped battery event {:?}", event);
+            }
         }
+        self.state = state;
+    }
+}
+
+#[cfg(test)]
+impl BatteryManagement {
+    /// Sets the cell voltage as if it was read by `process`
+    pub(crate) fn set_voltage(&mut self, mv: u16) {
+        self.mv = mv;
     }
 }
 
@@ -68,4 +179,74 @@ fn bodged_soc(raw: u16) -> u16 {
         soc = 100; // cap at 100
     }
     soc
-}
\ No newline at end of file
+}
+
+/// Linearly interpolate the percentage of `mv` between the points of the `curve`
+fn interpolate(curve: &[(u16, u8)], mv: u16) -> u8 {
+    let (first, last) = match (curve.first(), curve.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return 0,
+    };
+    if mv <= first.0 {
+        return first.1.min(100);
+    }
+    if mv >= last.0 {
+        return last.1.min(100);
+    }
+    for window in curve.windows(2) {
+        let ((lo_mv, lo_pct), (hi_mv, hi_pct)) = (window[0], window[1]);
+        if mv <= hi_mv {
+            let span = u32::from(hi_mv - lo_mv).max(1);
+            let offset = u32::from(mv - lo_mv);
+            let pct = if hi_pct >= lo_pct {
+                u32::from(lo_pct) + u32::from(hi_pct - lo_pct) * offset / span
+            } else {
+                u32::from(lo_pct) - u32::from(lo_pct - hi_pct) * offset / span
+            };
+            return (pct as u8).min(100);
+        }
+    }
+    last.1.min(100)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn percentage_from_curve() {
+        let curve = [(3000, 0), (3600, 10), (4000, 90), (4200, 100)];
+        assert_eq!(interpolate(&curve, 2500), 0);
+        assert_eq!(interpolate(&curve, 3000), 0);
+        assert_eq!(interpolate(&curve, 3300), 5);
+        assert_eq!(interpolate(&curve, 3600), 10);
+        assert_eq!(interpolate(&curve, 3800), 50);
+        assert_eq!(interpolate(&curve, 3900), 70);
+        assert_eq!(interpolate(&curve, 4100), 95);
+        assert_eq!(interpolate(&curve, 4200), 100);
+        assert_eq!(interpolate(&curve, 4400), 100);
+
+        assert_eq!(interpolate(&[(3000, 120)], 3500), 100);
+        assert_eq!(interpolate(&[], 3500), 0);
+    }
+
+    #[test]
+    fn charging_transitions() {
+        let mut bms = unsafe {
+            // safe because we dont use the hw in tests
+            BatteryManagement::new(core::mem::uninitialized(), core::mem::uninitialized(), core::mem::uninitialized())
+        };
+        assert!(!bms.is_charging());
+        bms.update_state(State::Charging);
+        bms.update_state(State::Charging);
+        assert!(bms.is_charging());
+        assert_eq!(bms.event(), Some(InputEvent::ChargingStarted));
+        assert_eq!(bms.event(), None);
+
+        bms.update_state(State::Charged);
+        bms.update_state(State::Draining);
+        assert!(!bms.is_charging());
+        assert_eq!(bms.event(), Some(InputEvent::ChargingStopped));
+        assert_eq!(bms.event(), None);
+    }
+}
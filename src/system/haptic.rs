@@ -0,0 +1,147 @@
+//! Haptic
+//!
+//! Drives the vibration motor through simple patterns, advanced by the system tick
+
+/// The buzz used to announce a new notification
+pub const NOTIFICATION_BUZZ_MS: u16 = 200;
+
+/// Hardware that can vibrate the watch
+pub trait Motor {
+    /// Turn the motor on or off
+    fn set(&mut self, on: bool);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pattern {
+    /// One buzz
+    Single,
+    /// Two buzzes, separated by a pause of the same length
+    Double,
+    /// One buzz, three times as long
+    Long,
+}
+
+impl Pattern {
+    /// The pattern of a syscall argument, 0 single, 1 double, 2 long
+    pub fn from_u8(pattern: u8) -> Option<Pattern> {
+        match pattern {
+            0 => Some(Pattern::Single),
+            1 => Some(Pattern::Double),
+            2 => Some(Pattern::Long),
+            _ => None,
+        }
+    }
+
+    /// The length of each step of the pattern in multiples of the buzz length, alternating on and off
+    fn steps(self) -> &'static [u32] {
+        match self {
+            Pattern::Single => &[1],
+            Pattern::Double => &[1, 1, 1],
+            Pattern::Long => &[3],
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Haptic {
+    motor: Option<&'static mut dyn Motor>,
+    /// the pattern being played, the buzz length and the current step
+    playing: Option<(Pattern, u32, usize)>,
+    /// the time the current step started
+    step_start_ms: u32,
+    now_ms: u32,
+}
+
+impl Haptic {
+
+    /// Sets the motor driven by the patterns
+    pub fn set_motor(&mut self, motor: &'static mut dyn Motor) {
+        self.motor = Some(motor);
+    }
+
+    /// Whether a motor has been set, without one the patterns are silent
+    pub fn has_motor(&self) -> bool {
+        self.motor.is_some()
+    }
+
+    /// Play `pattern` with a buzz length of `ms` from the last `tick`, replacing any pattern being played
+    pub fn vibrate(&mut self, ms: u16, pattern: Pattern) {
+        self.playing = Some((pattern, u32::from(ms), 0));
+        self.step_start_ms = self.now_ms;
+        self.set(true);
+    }
+
+    /// Whether a pattern is being played
+    pub fn is_vibrating(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    /// Advance the pattern to `now_ms`, the pattern steps are only as accurate as the tick
+    pub fn tick(&mut self, now_ms: u32) {
+        self.now_ms = now_ms;
+        let (pattern, ms, step) = match self.playing {
+            Some(playing) => playing,
+            None => return,
+        };
+        if now_ms.wrapping_sub(self.step_start_ms) < ms * pattern.steps()[step] {
+            return;
+        }
+        let step = step + 1;
+        match pattern.steps().get(step) {
+            Some(_) => {
+                self.playing = Some((pattern, ms, step));
+                self.step_start_ms = now_ms;
+                self.set(step % 2 == 0);
+            }
+            None => {
+                self.playing = None;
+                self.set(false);
+            }
+        }
+    }
+
+    fn set(&mut self, on: bool) {
+        if let Some(motor) = self.motor.as_mut() {
+            motor.set(on);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+    use std::vec::Vec;
+
+    struct MockMotor(&'static Cell<bool>);
+
+    impl Motor for MockMotor {
+        fn set(&mut self, on: bool) {
+            self.0.set(on);
+        }
+    }
+
+    /// Play the pattern, recording whether the motor is on every 100ms
+    fn play(ms: u16, pattern: Pattern) -> Vec<bool> {
+        let on: &'static Cell<bool> = Box::leak(Box::new(Cell::new(false)));
+        let mut haptic = Haptic::default();
+        haptic.set_motor(Box::leak(Box::new(MockMotor(on))));
+        haptic.vibrate(ms, pattern);
+        let mut states = Vec::new();
+        let mut now_ms = 0;
+        while haptic.is_vibrating() {
+            states.push(on.get());
+            now_ms += 100;
+            haptic.tick(now_ms);
+        }
+        assert!(!on.get());
+        states
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(play(200, Pattern::Single), [true, true]);
+        assert_eq!(play(100, Pattern::Double), [true, false, true]);
+        assert_eq!(play(100, Pattern::Long), [true, true, true]);
+    }
+}
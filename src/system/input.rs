@@ -4,8 +4,11 @@
 //! unique output combinations (7)
 
 use crate::types::InputEvent;
+use crate::system::system::TSC_HZ;
 use crate::types::{LeftButton, MiddleButton, RightButton, TouchSenseController};
-use crate::types::hal::tsc::Event as TscEvent;
+use crate::types::hal::tsc::{Event as TscEvent, Error as TscError};
+use heapless::consts::*;
+use heapless::spsc::Queue;
 
 pub const LEFT: u8 = 1;
 pub const MIDDLE: u8 = 2;
@@ -17,56 +20,659 @@ pub const ALL: u8 = LEFT | MIDDLE | RIGHT;
 pub const NONE: u8 = 0;
 
 pub const MAX_PIN_IDX: u8 = 2;
+pub const PIN_COUNT: usize = MAX_PIN_IDX as usize + 1;
+
+/// The default time an input must be held to generate a long press, e.g `InputEvent::LongPressLeft`
+pub const LONG_PRESS_MS: u32 = 1000;
+/// The default time all inputs must be held to generate a `InputEvent::ResetCombo`
+pub const RESET_COMBO_MS: u32 = 5000;
+/// The default time between releasing and pressing the middle input again to generate a `InputEvent::DoubleMiddle`
+pub const DOUBLE_TAP_MS: u32 = 400;
+/// The default time a finger has to cross all three inputs to generate a swipe
+pub const SWIPE_MS: u32 = 500;
+/// The default factor of the untouched baseline a reading must cross to register as a touch
+pub const CALIBRATION_FACTOR: f32 = 0.98;
+/// The default distance either side of the threshold a reading must cross to register a press or release
+pub const HYSTERESIS: u16 = 5;
+/// The default number of acquisitions of each pin used to calibrate
+pub const SAMPLES: u16 = 12;
+/// The fewest acquisitions that can be used to calibrate
+pub const MIN_SAMPLES: u16 = 1;
+/// The most acquisitions that can be used to calibrate, each one blocks for a full acquisition
+pub const MAX_SAMPLES: u16 = 256;
+/// The default time an acquisition may take before it is abandoned
+pub const ACQUISITION_TIMEOUT_MS: u32 = 100;
+/// The largest count the TSC can acquire, a threshold above this can never be crossed
+pub const MAX_THRESHOLD: u16 = 16383;
+/// The interval acquisitions are started at by the input timer
+pub const POLL_MS: u32 = 1000 / TSC_HZ;
+/// The time without a touch after which acquisition cycles back off, see `InputManager::set_duty_cycle`
+pub const IDLE_AFTER_MS: u32 = 5000;
+/// The interval between acquisition cycles while idle, see `InputManager::set_duty_cycle`
+pub const IDLE_POLL_MS: u32 = 250;
+/// The format version of a stored calibration, a calibration stored with another version is stale
+pub const CALIBRATION_VERSION: u8 = 1;
+/// The length of a stored calibration, the version followed by the baseline and threshold of each pin
+pub const CALIBRATION_LEN: usize = 1 + 4 * PIN_COUNT;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     NoInput,
     InvalidInputVector(u8),
     InvalidInputPin,
     AcquisitionInProgress,
-    Incomplete
+    Incomplete,
+    CalibrationFailed,
+    /// The sample count is outside of `MIN_SAMPLES..=MAX_SAMPLES`
+    InvalidSampleCount(u16),
+    /// The acquisition of the pin reached the max count, the pad is likely disconnected
+    AcquisitionFailed(u8),
+    /// The acquisition did not complete in time and was abandoned
+    AcquisitionTimeout,
+    /// The threshold is outside of `1..=MAX_THRESHOLD`
+    InvalidThreshold(u16),
+    /// The hardware cannot acquire all the pins together, see `AcquisitionMode::Grouped`
+    GroupedUnsupported,
+    /// The calibration could not be saved
+    StorageFailed,
+    /// The last acquisition of every pin failed, no input can be read
+    AllAcquisitionsFailed,
+}
+
+/// A small nonvolatile store, used to keep the calibration across reboots
+pub trait Storage {
+    /// Read the stored record into `buf`, returning its length, `None` if nothing is stored
+    fn load(&mut self, buf: &mut [u8]) -> Option<usize>;
+    /// Replace the stored record with `data`
+    fn save(&mut self, data: &[u8]) -> Result<(), Error>;
+}
+
+/// The event generated by each input vector, indexed by the vector (see `LEFT` etc.).
+/// All inputs being released is always reported as a release (see `RELEASE_MAPPING`), so the `NONE` entry is not used.
+pub const DEFAULT_MAPPING: [InputEvent; 8] = [
+    InputEvent::Multi,
+    InputEvent::Left,           // LEFT
+    InputEvent::Middle,         // MIDDLE
+    InputEvent::LeftMiddle,     // LEFT_MIDDLE
+    InputEvent::Right,          // RIGHT
+    InputEvent::Dual,           // LEFT_RIGHT
+    InputEvent::RightMiddle,    // RIGHT_MIDDLE
+    InputEvent::Multi,          // ALL
+];
+
+/// The long press event of each input vector, indexed by the vector (see `LEFT` etc.).
+/// Nothing can be held while no input is touched, so the `NONE` entry is not used.
+const LONG_PRESS_MAPPING: [InputEvent; 8] = [
+    InputEvent::LongPressMulti,
+    InputEvent::LongPressLeft,          // LEFT
+    InputEvent::LongPressMiddle,        // MIDDLE
+    InputEvent::LongPressLeftMiddle,    // LEFT_MIDDLE
+    InputEvent::LongPressRight,         // RIGHT
+    InputEvent::LongPressDual,          // LEFT_RIGHT
+    InputEvent::LongPressRightMiddle,   // RIGHT_MIDDLE
+    InputEvent::LongPressMulti,         // ALL
+];
+
+/// The event generated when all inputs are released, indexed by the vector that was touched before (see `LEFT` etc.).
+/// Releasing when nothing was touched is not an event, so the `NONE` entry is not used.
+const RELEASE_MAPPING: [InputEvent; 8] = [
+    InputEvent::ReleasedMulti,
+    InputEvent::ReleasedLeft,           // LEFT
+    InputEvent::ReleasedMiddle,         // MIDDLE
+    InputEvent::ReleasedLeftMiddle,     // LEFT_MIDDLE
+    InputEvent::ReleasedRight,          // RIGHT
+    InputEvent::ReleasedDual,           // LEFT_RIGHT
+    InputEvent::ReleasedRightMiddle,    // RIGHT_MIDDLE
+    InputEvent::ReleasedMulti,          // ALL
+];
+
+/// How a reading compares to the threshold when a pin is touched
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Polarity {
+    /// A touch reads below the threshold, as with the TSC
+    ActiveLow,
+    /// A touch reads above the threshold
+    ActiveHigh,
+}
+
+impl Default for Polarity {
+    fn default() -> Self {
+        Polarity::ActiveLow
+    }
+}
+
+/// How the pins are acquired to build an input vector
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AcquisitionMode {
+    /// One pin per acquisition, taking three acquisitions per vector
+    Sequential,
+    /// All the pins in a single acquisition, if the hardware supports it
+    Grouped,
+}
+
+impl Default for AcquisitionMode {
+    fn default() -> Self {
+        AcquisitionMode::Sequential
+    }
+}
+
+/// Touch sense hardware, acquiring the pins by index (left, middle, right)
+pub trait TouchSense {
+    /// Begin an acquisition of `pin`
+    fn start(&mut self, pin: u8);
+    /// Whether an acquisition is in progress
+    fn in_progress(&mut self) -> bool;
+    /// The count of the completed acquisition of `pin`, `None` if it reached the max count
+    fn read(&mut self, pin: u8) -> Option<u16>;
+    /// Clear any pending events of the last acquisition
+    fn clear(&mut self);
+    /// Acquire `pin`, blocking until the acquisition completes, `None` if it failed
+    fn acquire(&mut self, pin: u8) -> Option<u16>;
+    /// Begin an acquisition of all the pins together, returns false if the hardware can only acquire one pin at a time
+    fn start_all(&mut self) -> bool {
+        false
+    }
+    /// The counts of the completed acquisition of all the pins, see `read`
+    fn read_all(&mut self) -> [Option<u16>; PIN_COUNT] {
+        [None; PIN_COUNT]
+    }
+}
+
+/// The TSC peripheral and the pins of the three inputs
+pub struct HalTouchSense {
+    tsc: TouchSenseController,
+    left: LeftButton,
+    middle: MiddleButton,
+    right: RightButton,
+}
+
+impl HalTouchSense {
+    pub fn new(tsc: TouchSenseController, left: LeftButton, middle: MiddleButton, right: RightButton) -> Self {
+        let mut tsc = tsc;
+        tsc.listen(TscEvent::EndOfAcquisition);
+        tsc.listen(TscEvent::MaxCountError);
+        Self { tsc, left, middle, right }
+    }
+}
+
+impl TouchSense for HalTouchSense {
+    fn start(&mut self, pin: u8) {
+        match pin {
+            0 => self.tsc.start(&mut self.left),
+            1 => self.tsc.start(&mut self.middle),
+            2 => self.tsc.start(&mut self.right),
+            _ => panic!("Invalid pin index")
+        }
+    }
+
+    fn in_progress(&mut self) -> bool {
+        self.tsc.in_progress()
+    }
+
+    fn read(&mut self, pin: u8) -> Option<u16> {
+        let value = match pin {
+            0 => self.tsc.read(&mut self.left),
+            1 => self.tsc.read(&mut self.middle),
+            2 => self.tsc.read(&mut self.right),
+            _ => panic!("Invalid pin index")
+        };
+        self.clear();
+        match value {
+            Ok(value) => Some(value),
+            Err(TscError::MaxCountError) => None,
+            Err(err) => panic!("Expected TSC pin {} {:?}", pin, err),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.tsc.clear(TscEvent::MaxCountError);
+        self.tsc.clear(TscEvent::EndOfAcquisition);
+    }
+
+    fn acquire(&mut self, pin: u8) -> Option<u16> {
+        match pin {
+            0 => self.tsc.acquire(&mut self.left),
+            1 => self.tsc.acquire(&mut self.middle),
+            _ => self.tsc.acquire(&mut self.right),
+        }.map_err(|err| {
+            error!("TSC acquisition of pin {} failed {:?}", pin, err);
+        }).ok()
+    }
+}
+
+/// A calibration sampled by the acquisition cycles, see `InputManager::start_calibration`
+struct Calibration {
+    samples: u16,
+    /// the number of readings of each pin so far
+    taken: [u16; PIN_COUNT],
+    totals: [u32; PIN_COUNT],
+}
+
+impl Calibration {
+    fn new(samples: u16) -> Self {
+        Self { samples, taken: [0; PIN_COUNT], totals: [0; PIN_COUNT] }
+    }
+
+    fn add(&mut self, pin: usize, value: u16) {
+        if self.taken[pin] < self.samples {
+            self.taken[pin] += 1;
+            self.totals[pin] += u32::from(value);
+        }
+    }
+
+    /// The average reading of each pin, once every pin has been sampled enough
+    fn baseline(&self) -> Option<[u16; PIN_COUNT]> {
+        if self.taken.iter().any(|&taken| taken < self.samples) {
+            return None;
+        }
+        let mut baseline = [0; PIN_COUNT];
+        for (pin, total) in self.totals.iter().enumerate() {
+            baseline[pin] = (total / u32::from(self.samples)) as u16;
+        }
+        Some(baseline)
+    }
 }
 
 /// Input manager, assumes control over the tsc peripheral and handles the raw inputs
-pub struct InputManager
+pub struct InputManager<T: TouchSense = HalTouchSense>
 {
     raw_vector: u8,
     last_vector: u8,
-    tsc: TscManager,
+    tsc: TscManager<T>,
+    polarity: Polarity,
+    mode: AcquisitionMode,
     pin_idx: u8,
+    /// consecutive samples per pin that disagree with the committed state
+    pending: [u8; PIN_COUNT],
+    debounce: u8,
+    now_ms: u32,
+    held_since_ms: u32,
+    long_press_ms: u32,
+    long_press_sent: bool,
+    reset_combo_ms: u32,
+    reset_combo_sent: bool,
+    middle_taps: u8,
+    middle_released_ms: u32,
+    double_tap_ms: u32,
+    /// the time of the most recent activation of each pin
+    activations: [Option<u32>; PIN_COUNT],
+    swiping: bool,
+    swipe_ms: u32,
+    acquiring: bool,
+    acquisition_start_ms: u32,
+    acquisition_timeout_ms: u32,
+    events: Queue<InputEvent, U8>,
+    /// the time `output` last produced an event
+    last_event_ms: u32,
+    /// (initial delay, interval) of repeated events while a single input is held
+    repeat: Option<(u32, u32)>,
+    /// time since the press the next repeat is due
+    repeat_due_ms: u32,
+    mapping: [InputEvent; 8],
+    /// (idle after, idle interval) of the acquisition cycles
+    duty_cycle: Option<(u32, u32)>,
+    poll_ms: u32,
+    last_touch_ms: u32,
+    cycle_start_ms: u32,
+    calibration: Option<Calibration>,
+    /// the outcome of the last calibration, until it is taken
+    calibrated: Option<Result<[u16; PIN_COUNT], Error>>,
 }
 
-impl InputManager {
-    /// Creates a new instance of the InputManager
-    pub fn new(tsc: TscManager) -> Self {
+impl<T: TouchSense> InputManager<T> {
+    /// Creates a new instance of the InputManager, a pin must read the same for `debounce` consecutive 
+    /// samples before it changes state. `polarity` decides which side of the threshold is a touch, and an input
+    /// held for `long_press_ms` generates a long press, e.g `InputEvent::LongPressLeft` (see `LONG_PRESS_MS`).
+    pub fn new(tsc: TscManager<T>, debounce: u8, polarity: Polarity, long_press_ms: u32) -> Self {
         
         Self {
             raw_vector: 0,
             last_vector: 0,
             pin_idx: 0,
-            tsc: tsc
+            tsc: tsc,
+            polarity,
+            mode: AcquisitionMode::default(),
+            pending: [0; PIN_COUNT],
+            debounce: if debounce == 0 { 1 } else { debounce },
+            now_ms: 0,
+            held_since_ms: 0,
+            long_press_ms,
+            long_press_sent: false,
+            reset_combo_ms: RESET_COMBO_MS,
+            reset_combo_sent: false,
+            middle_taps: 0,
+            middle_released_ms: 0,
+            double_tap_ms: DOUBLE_TAP_MS,
+            activations: [None; PIN_COUNT],
+            swiping: false,
+            swipe_ms: SWIPE_MS,
+            acquiring: false,
+            acquisition_start_ms: 0,
+            acquisition_timeout_ms: ACQUISITION_TIMEOUT_MS,
+            events: Queue::new(),
+            last_event_ms: 0,
+            repeat: None,
+            repeat_due_ms: 0,
+            mapping: DEFAULT_MAPPING,
+            duty_cycle: None,
+            poll_ms: POLL_MS,
+            last_touch_ms: 0,
+            cycle_start_ms: 0,
+            calibration: None,
+            calibrated: None,
         }
     }
 
-    pub fn start_new(&mut self) -> Result<(), Error>{
-        self.tsc.start(self.pin_idx)?;
+    /// Creates a new instance of the InputManager as with `new`, restoring the calibration from `storage`,
+    /// see `TscManager::restore`
+    pub fn with_storage(tsc: TscManager<T>, debounce: u8, polarity: Polarity, long_press_ms: u32, storage: &mut dyn Storage) -> Result<Self, Error> {
+        let mut tsc = tsc;
+        tsc.restore(storage)?;
+        Ok(Self::new(tsc, debounce, polarity, long_press_ms))
+    }
+
+    /// Sets the time an input must be held to generate a long press, e.g `InputEvent::LongPressLeft`
+    pub fn set_long_press(&mut self, ms: u32) {
+        self.long_press_ms = ms;
+    }
+
+    /// Sets the time all inputs must be held to generate a `InputEvent::ResetCombo`
+    pub fn set_reset_combo(&mut self, ms: u32) {
+        self.reset_combo_ms = ms;
+    }
+
+    /// Sets the maximum time between middle taps to generate a `InputEvent::DoubleMiddle`
+    pub fn set_double_tap(&mut self, ms: u32) {
+        self.double_tap_ms = ms;
+    }
+
+    /// Sets the time a finger has to cross all three inputs to generate a swipe
+    pub fn set_swipe(&mut self, ms: u32) {
+        self.swipe_ms = ms;
+    }
+
+    /// Repeat the event of a single held input after `delay_ms`, then every `interval_ms` until it is released.
+    /// Disabled by default, pass `None` to disable.
+    pub fn set_repeat(&mut self, repeat: Option<(u32, u32)>) {
+        self.repeat = repeat;
+    }
+
+    /// Back off to starting an acquisition cycle every `interval_ms` once no touch has been seen for `idle_ms`,
+    /// returning to polling every tick on the first touch. Disabled by default, pass `None` to disable.
+    pub fn set_duty_cycle(&mut self, duty_cycle: Option<(u32, u32)>) {
+        self.duty_cycle = duty_cycle;
+    }
+
+    /// Sets the interval `start_new` is called at, reported while not idle
+    pub fn set_poll_interval(&mut self, ms: u32) {
+        self.poll_ms = ms;
+    }
+
+    /// Sets the event generated by each input vector, see `DEFAULT_MAPPING`
+    pub fn set_mapping(&mut self, mapping: [InputEvent; 8]) {
+        self.mapping = mapping;
+    }
+
+    /// Sets the number of acquisitions of each pin used to calibrate, see `TscManager::set_samples`
+    pub fn set_samples(&mut self, samples: u16) -> Result<(), Error> {
+        self.tsc.set_samples(samples)
+    }
+
+    /// The number of acquisitions of each pin used to calibrate, see `TscManager::samples`
+    pub fn samples(&self) -> u16 {
+        self.tsc.samples()
+    }
+
+    /// Sets the factor of the baseline used as the threshold by the next `calibrate`, see `TscManager::set_factor`
+    pub fn set_factor(&mut self, factor: f32) {
+        self.tsc.set_factor(factor)
+    }
+
+    /// Recalibrate the touch thresholds from `samples` acquisitions of each pin, the inputs must not be touched.
+    /// Blocks for every acquisition, so at runtime use `start_calibration` instead
+    pub fn calibrate(&mut self, samples: u16) -> Result<[u16; PIN_COUNT], Error> {
+        self.tsc.calibrate(samples)
+    }
+
+    /// Recalibrate the touch thresholds from the next `samples` acquisitions of each pin, the inputs must not be touched.
+    /// The readings are taken by `process_result` instead of updating the inputs, without blocking, and the idle
+    /// duty cycle is suspended until the calibration is done. The outcome is collected with `take_calibration`
+    pub fn start_calibration(&mut self, samples: u16) -> Result<(), Error> {
+        if !(MIN_SAMPLES..=MAX_SAMPLES).contains(&samples) {
+            return Err(Error::InvalidSampleCount(samples));
+        }
+        self.calibration = Some(Calibration::new(samples));
+        self.calibrated = None;
         Ok(())
     }
 
+    /// Whether a calibration started with `start_calibration` is still sampling
+    pub fn is_calibrating(&self) -> bool {
+        self.calibration.is_some()
+    }
+
+    /// The new thresholds once a calibration started with `start_calibration` is done, or
+    /// `Error::CalibrationFailed` if an acquisition failed while sampling
+    pub fn take_calibration(&mut self) -> Option<Result<[u16; PIN_COUNT], Error>> {
+        self.calibrated.take()
+    }
+
+    /// Sets how the pins are acquired, `AcquisitionMode::Grouped` fails in `start_new` if the hardware does not support it
+    pub fn set_acquisition_mode(&mut self, mode: AcquisitionMode) {
+        self.mode = mode;
+        self.pin_idx = 0;
+    }
+
+    /// Sets the time an acquisition may take before `poll` abandons it
+    pub fn set_acquisition_timeout(&mut self, ms: u32) {
+        self.acquisition_timeout_ms = ms;
+    }
+
+    /// Update the managers notion of time
+    pub fn tick(&mut self, now_ms: u32) {
+        self.now_ms = now_ms;
+    }
+
+    /// Start the acquisition of the next pin, or all the pins in `AcquisitionMode::Grouped`. While idle (see `set_duty_cycle`)
+    /// new cycles are only started every idle interval, returns the time until the next acquisition is due
+    pub fn start_new(&mut self) -> Result<u32, Error>{
+        if self.pin_idx == 0 && !self.acquiring {
+            if let Some(remaining) = self.idle_backoff() {
+                return Ok(remaining);
+            }
+            self.cycle_start_ms = self.now_ms;
+        }
+        let result = match self.mode {
+            AcquisitionMode::Sequential => self.tsc.start(self.pin_idx),
+            AcquisitionMode::Grouped => self.tsc.start_all(),
+        };
+        // an acquisition still in progress after a timeout is timed again
+        if result.is_ok() || !self.acquiring {
+            self.acquiring = true;
+            self.acquisition_start_ms = self.now_ms;
+        }
+        result?;
+        // the pins of a cycle are acquired at the full rate
+        let last = self.pin_idx == MAX_PIN_IDX || self.mode == AcquisitionMode::Grouped;
+        let backoff = if last { self.idle_backoff() } else { None };
+        Ok(backoff.unwrap_or(0).max(self.poll_ms))
+    }
+
+    /// Whether no touch has been seen for the idle time of the duty cycle
+    pub fn is_idle(&self) -> bool {
+        match self.duty_cycle {
+            Some((idle_ms, _)) => self.raw_vector == NONE && self.now_ms.wrapping_sub(self.last_touch_ms) >= idle_ms,
+            None => false,
+        }
+    }
+
+    /// The time until the next cycle is due while idle, `None` if a cycle is due now
+    fn idle_backoff(&self) -> Option<u32> {
+        let (_, interval_ms) = self.duty_cycle?;
+        if !self.is_idle() || self.is_calibrating() {
+            return None;
+        }
+        let remaining = interval_ms.saturating_sub(self.now_ms.wrapping_sub(self.cycle_start_ms));
+        if remaining > 0 { Some(remaining) } else { None }
+    }
+
+    /// Abandon the current acquisition if it has been in progress longer than the timeout, moving on to the next pin
+    pub fn poll(&mut self) -> Result<(), Error> {
+        if self.acquisition_timed_out() {
+            self.tsc.abort();
+            return Err(Error::AcquisitionTimeout);
+        }
+        Ok(())
+    }
+
+    fn acquisition_timed_out(&mut self) -> bool {
+        if self.acquiring && self.now_ms.wrapping_sub(self.acquisition_start_ms) > self.acquisition_timeout_ms {
+            warn!("TSC acquisition of pin {} timed out", self.pin_idx);
+            self.acquiring = false;
+            if self.mode == AcquisitionMode::Sequential {
+                self.next_pin();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn process_result(&mut self) -> Result<(), Error> {
-        let result = self.tsc.result(self.pin_idx);
-        self.update_input(result);
+        self.acquiring = false;
+        if self.is_calibrating() {
+            self.sample();
+            // no input is evaluated while calibrating, but the cycle still completes
+            return if self.pin_idx == 0 { Ok(()) } else { Err(Error::Incomplete) };
+        }
+        if self.mode == AcquisitionMode::Grouped {
+            let results = self.tsc.result_all(self.polarity);
+            return self.handle_results(results);
+        }
+        let result = self.tsc.result(self.pin_idx, self.polarity);
+        self.handle_result(result)
+    }
 
-        if self.pin_idx == MAX_PIN_IDX { // we've read all the pins now process the output
-            Ok(())
+    /// Add the readings of the completed acquisition to the calibration, the inputs keep their state
+    fn sample(&mut self) {
+        let mut values = [None; PIN_COUNT];
+        let pins = match self.mode {
+            AcquisitionMode::Grouped => {
+                values = self.tsc.read_all();
+                0..=MAX_PIN_IDX
+            }
+            AcquisitionMode::Sequential => {
+                let pin = self.pin_idx;
+                values[pin as usize] = self.tsc.read(pin);
+                self.next_pin();
+                pin..=pin
+            }
+        };
+        let calibration = match self.calibration.as_mut() {
+            Some(calibration) => calibration,
+            None => return,
+        };
+        for pin in pins {
+            match values[pin as usize] {
+                Some(value) => calibration.add(pin as usize, value),
+                None => {
+                    error!("Failed to calibrate tsc pin {}", pin);
+                    self.calibration = None;
+                    self.calibrated = Some(Err(Error::CalibrationFailed));
+                    return;
+                }
+            }
+        }
+        if let Some(baseline) = calibration.baseline() {
+            self.calibration = None;
+            self.calibrated = Some(Ok(self.tsc.set_baseline(baseline)));
+        }
+    }
+
+    /// Update every pin from a grouped acquisition, failed pins are treated as untouched.
+    /// Returns the first failure, if any, once the cycle is complete
+    fn handle_results(&mut self, results: [Result<bool, Error>; PIN_COUNT]) -> Result<(), Error> {
+        let mut failed = None;
+        for result in results.iter() {
+            match *result {
+                Ok(active) => self.update_input(active),
+                Err(err) => {
+                    self.update_input(false);
+                    failed = failed.or(Some(err));
+                }
+            }
+        }
+        self.complete_cycle()?;
+        failed.map_or(Ok(()), Err)
+    }
+
+    fn handle_result(&mut self, result: Result<bool, Error>) -> Result<(), Error> {
+        match result {
+            Ok(active) => self.update_input(active),
+            Err(err) => {
+                // a stuck pad must not hold the input, release it and move on
+                self.update_input(false);
+                if self.pin_idx == 0 {
+                    self.complete_cycle()?;
+                }
+                return Err(err);
+            }
+        }
+
+        if self.pin_idx == 0 { // we've read all the pins now process the output
+            self.complete_cycle()
         } else {
             Err(Error::Incomplete)
         }
     }
 
+    /// Evaluate the inputs once all the pins have been read, queueing any resulting event
+    fn complete_cycle(&mut self) -> Result<(), Error> {
+        match self.evaluate() {
+            Ok(event) => {
+                self.events.enqueue(event).unwrap_or_else(|event| {
+                    warn!("Input queue full, dropping {:?}", event);
+                });
+                Ok(())
+            }
+            Err(Error::NoInput) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
     /// Update thes the internal state of the manager with the raw hardware input
     pub fn update_input(&mut self, active: bool) {
         if active {
+            self.last_touch_ms = self.now_ms;
+        }
+        let idx = self.pin_idx as usize;
+        let committed = self.raw_vector & (1 << self.pin_idx) != 0;
+        if active == committed {
+            self.pending[idx] = 0;
+        } else {
+            self.pending[idx] += 1;
+            if self.pending[idx] >= self.debounce {
+                self.pending[idx] = 0;
+                self.commit_input(active);
+            }
+        }
+
+        // update the index once the input has been set
+        self.next_pin();
+    }
+
+    fn next_pin(&mut self) {
+        self.pin_idx += 1;
+        if self.pin_idx > MAX_PIN_IDX {
+            self.pin_idx = 0;
+        }
+    }
+
+    /// Set or clear the bit of the current pin in the raw vector
+    fn commit_input(&mut self, active: bool) {
+        if active {
+            self.activations[self.pin_idx as usize] = Some(self.now_ms);
             self.raw_vector |= match self.pin_idx {
                 0 => 1 ,
                 1 => 1 << 1,
@@ -81,128 +687,1200 @@ impl InputManager {
                 _ => panic!("Invalid pin index")
             };
         }
-        
-        // update the index once the input has been set
-        self.pin_idx += 1;
-        if self.pin_idx > 2 {
-            self.pin_idx = 0;
-        }
     }
 
-    /// Based on the current state of the inputmanager's internal vector, produce an output
+    /// Take the oldest queued input event, once the queue is drained `Error::AllAcquisitionsFailed`
+    /// is returned while every pin is failing
     pub fn output(&mut self) -> Result<InputEvent, Error> {
+        let event = self.events.dequeue().ok_or(if self.tsc.errored() == ALL {
+            Error::AllAcquisitionsFailed
+        } else {
+            Error::NoInput
+        })?;
+        self.last_event_ms = self.now_ms;
+        Ok(event)
+    }
+
+    /// The time `output` last produced an event, i.e the last user interaction, 0 if there has been none
+    pub fn last_event_ms(&self) -> u32 {
+        self.last_event_ms
+    }
+
+    /// Based on the current state of the inputmanager's internal vector, produce an output
+    fn evaluate(&mut self) -> Result<InputEvent, Error> {
+        if let Some(swipe) = self.detect_swipe() {
+            // the rest of the swipe is not reported until the finger is lifted
+            self.swiping = self.raw_vector != NONE;
+            self.last_vector = self.raw_vector;
+            self.middle_taps = 0;
+            return Ok(swipe);
+        }
+        if self.swiping {
+            self.swiping = self.raw_vector != NONE;
+            self.last_vector = self.raw_vector;
+            return Err(Error::NoInput);
+        }
         if self.raw_vector != self.last_vector {
+            let double_tap = self.track_double_tap();
             let result = match self.raw_vector {
-                ALL => Ok(InputEvent::Multi),
-                LEFT_RIGHT => Ok(InputEvent::Dual),
-                LEFT_MIDDLE => Ok(InputEvent::LeftMiddle),
-                RIGHT_MIDDLE => Ok(InputEvent::RightMiddle),
-                LEFT => Ok(InputEvent::Left),
-                MIDDLE => Ok(InputEvent::Middle),
-                RIGHT => Ok(InputEvent::Right),
-                NONE => Err(Error::NoInput), // no input
-                _ => Err(Error::InvalidInputVector(self.raw_vector)),
+                MIDDLE if double_tap => Ok(InputEvent::DoubleMiddle),
+                NONE => RELEASE_MAPPING.get(self.last_vector as usize).cloned()
+                    .ok_or(Error::InvalidInputVector(self.last_vector)),
+                vector => self.mapping.get(vector as usize).cloned()
+                    .ok_or(Error::InvalidInputVector(vector)),
             };
             self.last_vector = self.raw_vector;
+            self.held_since_ms = self.now_ms;
+            self.long_press_sent = false;
+            self.reset_combo_sent = false;
+            self.repeat_due_ms = self.repeat.map(|(delay, _)| delay).unwrap_or(0);
             result
+        } else if self.raw_vector != NONE && !self.long_press_sent
+            && self.now_ms.wrapping_sub(self.held_since_ms) >= self.long_press_ms {
+            self.long_press_sent = true;
+            self.middle_taps = 0; // a held press is not a tap
+            LONG_PRESS_MAPPING.get(self.raw_vector as usize).cloned()
+                .ok_or(Error::InvalidInputVector(self.raw_vector))
+        } else if self.raw_vector == ALL && !self.reset_combo_sent
+            && self.now_ms.wrapping_sub(self.held_since_ms) >= self.reset_combo_ms {
+            // releasing any input changes the vector, restarting the hold
+            self.reset_combo_sent = true;
+            Ok(InputEvent::ResetCombo)
+        } else {
+            self.repeat_event().ok_or(Error::NoInput)
+        }
+    }
+
+    /// The event to repeat for the held input, if one is due
+    fn repeat_event(&mut self) -> Option<InputEvent> {
+        let (_, interval) = self.repeat?;
+        let event = match self.raw_vector {
+            LEFT | MIDDLE | RIGHT => self.mapping[self.raw_vector as usize],
+            _ => return None, // only single inputs repeat
+        };
+        if self.now_ms.wrapping_sub(self.held_since_ms) >= self.repeat_due_ms {
+            self.repeat_due_ms += interval;
+            self.middle_taps = 0; // a held press is not a tap
+            Some(event)
+        } else {
+            None
+        }
+    }
+
+    /// Detect if the latest pin activation completed a crossing of all the inputs in order.
+    /// Pins activated in the same acquisition cycle may have been touched in either order.
+    fn detect_swipe(&mut self) -> Option<InputEvent> {
+        if let [Some(left), Some(middle), Some(right)] = self.activations {
+            if self.raw_vector == ALL {
+                // overlapping touches, not a swipe
+                self.activations = [None; PIN_COUNT];
+                return None;
+            }
+            let ordered = |first: u32, last: u32| {
+                first != last && last.wrapping_sub(first) <= self.swipe_ms
+                    && middle.wrapping_sub(first) <= last.wrapping_sub(first)
+            };
+            let swipe = if ordered(left, right) {
+                Some(InputEvent::SwipeRight)
+            } else if ordered(right, left) {
+                Some(InputEvent::SwipeLeft)
+            } else {
+                None
+            };
+            if swipe.is_some() {
+                self.activations = [None; PIN_COUNT];
+            }
+            swipe
         } else {
-            Err(Error::NoInput)
+            None
         }
     }
 
-    /// returns the threshold value required to identify a touch
+    /// Track taps of the middle input across a vector change, returns true if the change completes a double tap
+    fn track_double_tap(&mut self) -> bool {
+        match (self.last_vector, self.raw_vector) {
+            (NONE, MIDDLE) => {
+                if self.middle_taps == 1 && self.now_ms.wrapping_sub(self.middle_released_ms) <= self.double_tap_ms {
+                    self.middle_taps = 2;
+                    true
+                } else {
+                    self.middle_taps = 1;
+                    false
+                }
+            }
+            (MIDDLE, NONE) => {
+                if self.middle_taps == 2 {
+                    self.middle_taps = 0;
+                } else {
+                    self.middle_released_ms = self.now_ms;
+                }
+                false
+            }
+            (_, NONE) => false,
+            _ => {
+                // any other input cancels the double tap
+                self.middle_taps = 0;
+                false
+            }
+        }
+    }
+
+    /// returns the threshold value required to identify a touch of the middle pin, see `thresholds`
     pub fn threshold(&self) -> u16 {
         self.tsc.threshold()
     }
+
+    /// returns the threshold values of each pin required to identify a touch
+    pub fn thresholds(&self) -> [u16; PIN_COUNT] {
+        self.tsc.thresholds()
+    }
+
+    /// Sets the threshold of every pin, see `TscManager::set_threshold`
+    pub fn set_threshold(&mut self, threshold: u16) -> Result<(), Error> {
+        self.tsc.set_threshold(threshold)
+    }
+
+    /// The last reading of each pin, useful for tuning the thresholds
+    pub fn last_raw(&self) -> [u16; PIN_COUNT] {
+        self.tsc.last_raw()
+    }
 }
 
-pub struct TscManager {
-    tsc: TouchSenseController,
-    left: LeftButton,
-    middle: MiddleButton,
-    right: RightButton,
-    tsc_threshold: u16,
+pub struct TscManager<T: TouchSense = HalTouchSense> {
+    sense: T,
+    tsc_threshold: [u16; PIN_COUNT],
+    baseline: [u16; PIN_COUNT],
+    factor: f32,
+    /// vector of pins whose last acquisition failed
+    errored: u8,
+    /// vector of pins currently touched
+    touched: u8,
+    hysteresis: u16,
+    samples: u16,
+    last_raw: [u16; PIN_COUNT],
 }
 
-impl TscManager{
+impl<T: TouchSense> TscManager<T> {
 
-    pub fn new(tsc: TouchSenseController, threshold: u16, left: LeftButton, middle: MiddleButton, right: RightButton) -> Self {
-        let mut tsc = tsc;
-        tsc.listen(TscEvent::EndOfAcquisition);
-        // tsc.listen(TscEvent::MaxCountError); // TODO
+    /// Creates a new TscManager, using the same threshold for all pins and a reading must cross `hysteresis`
+    /// either side of it to register a press or release (see `HYSTERESIS`)
+    pub fn new(sense: T, threshold: u16, hysteresis: u16) -> Self {
+        Self::with_thresholds(sense, [threshold; PIN_COUNT], hysteresis)
+    }
 
+    /// Creates a new TscManager, with a threshold per pin (left, middle, right) and `hysteresis` as with `new`
+    pub fn with_thresholds(sense: T, thresholds: [u16; PIN_COUNT], hysteresis: u16) -> Self {
         Self {
-            tsc,
-            tsc_threshold: threshold,
-            left,
-            middle,
-            right,
+            sense,
+            tsc_threshold: thresholds,
+            baseline: [0; PIN_COUNT],
+            factor: CALIBRATION_FACTOR,
+            errored: 0,
+            touched: 0,
+            hysteresis,
+            samples: SAMPLES,
+            last_raw: [0; PIN_COUNT],
         }
     }
 
     /// Begin a new hardware (tsc) acquisition
     pub fn start(&mut self, pin: u8) -> Result<(), Error> {
-        if self.tsc.in_progress() {
+        if self.sense.in_progress() {
             return Err(Error::AcquisitionInProgress);
         }
-        match pin {
-            0 => self.tsc.start(&mut self.left),
-            1 => self.tsc.start(&mut self.middle),
-            2 => self.tsc.start(&mut self.right),
-            _ => panic!("Invalid pin index")
+        self.sense.start(pin);
+        Ok(())
+    }
+
+    /// Begin a hardware acquisition of all the pins together
+    pub fn start_all(&mut self) -> Result<(), Error> {
+        if self.sense.in_progress() {
+            return Err(Error::AcquisitionInProgress);
+        }
+        if !self.sense.start_all() {
+            return Err(Error::GroupedUnsupported);
         }
         Ok(())
     }
 
+    /// Call when a grouped acquisition is complete, the result of each pin as with `result`
+    pub fn result_all(&mut self, polarity: Polarity) -> [Result<bool, Error>; PIN_COUNT] {
+        let values = self.sense.read_all();
+        let mut results = [Ok(false); PIN_COUNT];
+        for (pin, value) in values.iter().enumerate() {
+            results[pin] = self.check_value(pin as u8, *value, polarity);
+        }
+        results
+    }
+
     /// Call when the aquisition is complete, this function read
     /// the registers and update the interal state
-    pub fn result(&mut self, pin: u8) -> bool {
-        let value = match pin {
-            0 => self.tsc.read(&mut self.left).expect("Expected TSC pin 0"),
-            1 => self.tsc.read(&mut self.middle).expect("Expected TSC pin 1"),
-            2 => self.tsc.read(&mut self.right).expect("Expected TSC pin 2"),
-            _ => panic!("Invalid pin index")
+    pub fn result(&mut self, pin: u8, polarity: Polarity) -> Result<bool, Error> {
+        let value = self.sense.read(pin);
+        self.check_value(pin, value, polarity)
+    }
+
+    /// Call when the acquisition of `pin` is complete, the raw count without checking it for a touch,
+    /// `None` if it reached the max count
+    pub fn read(&mut self, pin: u8) -> Option<u16> {
+        self.sense.read(pin)
+    }
+
+    /// Call when a grouped acquisition is complete, the raw count of each pin as with `read`
+    pub fn read_all(&mut self) -> [Option<u16>; PIN_COUNT] {
+        self.sense.read_all()
+    }
+
+    /// Clear any pending events of an acquisition that is being abandoned
+    pub fn abort(&mut self) {
+        self.sense.clear();
+    }
+
+    /// Check the result of an acquisition, recording the pin as errored if it reached the max count
+    fn check_value(&mut self, pin: u8, value: Option<u16>, polarity: Polarity) -> Result<bool, Error> {
+        match value {
+            Some(value) => {
+                self.last_raw[pin as usize] = value;
+                self.errored &= !(1 << pin);
+                Ok(self.is_touched(pin, value, polarity))
+            }
+            None => {
+                warn!("TSC max count reached on pin {}", pin);
+                self.errored |= 1 << pin;
+                Err(Error::AcquisitionFailed(pin))
+            }
+        }
+    }
+
+    /// The vector of pins whose last acquisition failed
+    pub fn errored(&self) -> u8 {
+        self.errored
+    }
+
+    /// The last reading of each pin, touched or not
+    pub fn last_raw(&self) -> [u16; PIN_COUNT] {
+        self.last_raw
+    }
+
+    /// Whether the `value` read from `pin` is a touch
+    ///
+    /// With `Polarity::ActiveLow` a touch is registered below `threshold - hysteresis`, and released above
+    /// `threshold + hysteresis`, `Polarity::ActiveHigh` is the reverse. Between the two the pin keeps its previous state
+    fn is_touched(&mut self, pin: u8, value: u16, polarity: Polarity) -> bool {
+        let threshold = self.tsc_threshold[pin as usize];
+        let mask = 1 << pin;
+        trace!("tsc[{}] {} {:?} {}?", pin, value, polarity, threshold);
+        let below = value < threshold.saturating_sub(self.hysteresis);
+        let above = value > threshold.saturating_add(self.hysteresis);
+        let (press, release) = match polarity {
+            Polarity::ActiveLow => (below, above),
+            Polarity::ActiveHigh => (above, below),
         };
-        trace!("tsc[{}] {} < {}?", pin, value, self.tsc_threshold);
-        //self.update_input(value < self.tsc_threshold);
-        self.tsc.clear(TscEvent::EndOfAcquisition);
+        if press {
+            self.touched |= mask;
+        } else if release {
+            self.touched &= !mask;
+        }
+        self.touched & mask != 0
+    }
 
-        value < self.tsc_threshold
+    /// Sets the distance either side of the threshold a reading must cross to register a press or release
+    pub fn set_hysteresis(&mut self, hysteresis: u16) {
+        self.hysteresis = hysteresis;
     }
 
-    /// returns the threshold value required to identify a touch
+    /// returns the threshold value required to identify a touch of the middle pin, see `thresholds`
     pub fn threshold(&self) -> u16 {
+        self.thresholds()[1]
+    }
+
+    /// returns the threshold values of each pin required to identify a touch
+    pub fn thresholds(&self) -> [u16; PIN_COUNT] {
+        self.tsc_threshold
+    }
+
+    /// Sets the same threshold for all pins, replacing any calibrated thresholds
+    pub fn set_threshold(&mut self, threshold: u16) -> Result<(), Error> {
+        if threshold == 0 || threshold > MAX_THRESHOLD {
+            return Err(Error::InvalidThreshold(threshold));
+        }
+        self.tsc_threshold = [threshold; PIN_COUNT];
+        Ok(())
+    }
+
+    /// Sets the factor of the baseline used as the threshold by the next `calibrate`, below `1.0` for
+    /// `Polarity::ActiveLow` pads and above it for `Polarity::ActiveHigh`
+    pub fn set_factor(&mut self, factor: f32) {
+        self.factor = factor;
+    }
+
+    /// The untouched baseline of each pin measured by the last `calibrate`, 0 if not calibrated
+    pub fn baseline(&self) -> [u16; PIN_COUNT] {
+        self.baseline
+    }
+
+    /// Sets the number of acquisitions of each pin used by `restore`, trading accuracy for the time taken
+    pub fn set_samples(&mut self, samples: u16) -> Result<(), Error> {
+        if samples < MIN_SAMPLES || samples > MAX_SAMPLES {
+            return Err(Error::InvalidSampleCount(samples));
+        }
+        self.samples = samples;
+        Ok(())
+    }
+
+    /// The number of acquisitions of each pin used by `restore`
+    pub fn samples(&self) -> u16 {
+        self.samples
+    }
+
+    /// Calibrate the thresholds from `samples` acquisitions of each pin, which must not be touched.
+    ///
+    /// The baseline of a pin is its average reading and its threshold is the baseline multiplied by the
+    /// factor, see `set_factor`. Returns the new thresholds. Fails with `Error::AcquisitionInProgress`
+    /// if an acquisition has been started and not read
+    pub fn calibrate(&mut self, samples: u16) -> Result<[u16; PIN_COUNT], Error> {
+        if !(MIN_SAMPLES..=MAX_SAMPLES).contains(&samples) {
+            return Err(Error::InvalidSampleCount(samples));
+        }
+        if self.sense.in_progress() {
+            return Err(Error::AcquisitionInProgress);
+        }
+        let sense = &mut self.sense;
+        let baseline = measure_baseline(samples, |pin| {
+            sense.acquire(pin).ok_or_else(|| {
+                error!("Failed to calibrate tsc pin {}", pin);
+                Error::CalibrationFailed
+            })
+        })?;
+        Ok(self.set_baseline(baseline))
+    }
+
+    /// Load the calibration from `storage`, only calibrating if it is absent or stale, in which case the new
+    /// calibration is saved. Returns the thresholds
+    pub fn restore(&mut self, storage: &mut dyn Storage) -> Result<[u16; PIN_COUNT], Error> {
+        let mut record = [0u8; CALIBRATION_LEN];
+        let stored = storage.load(&mut record)
+            .and_then(|len| decode_calibration(&record[..len]));
+        if let Some((baseline, thresholds)) = stored {
+            info!("TSC calibration restored, baseline {:?}, thresholds {:?}", baseline, thresholds);
+            self.baseline = baseline;
+            self.tsc_threshold = thresholds;
+            return Ok(thresholds);
+        }
+        let thresholds = self.calibrate(self.samples)?;
+        storage.save(&encode_calibration(self.baseline, thresholds))?;
+        Ok(thresholds)
+    }
+
+    fn set_baseline(&mut self, baseline: [u16; PIN_COUNT]) -> [u16; PIN_COUNT] {
+        self.baseline = baseline;
+        for (threshold, baseline) in self.tsc_threshold.iter_mut().zip(baseline.iter()) {
+            // rounded to the nearest count
+            *threshold = (f32::from(*baseline) * self.factor + 0.5) as u16;
+        }
+        info!("TSC baseline {:?}, thresholds {:?}", baseline, self.tsc_threshold);
         self.tsc_threshold
     }
 }
 
+/// Encode the `baseline` and `thresholds` of each pin as a stored calibration
+fn encode_calibration(baseline: [u16; PIN_COUNT], thresholds: [u16; PIN_COUNT]) -> [u8; CALIBRATION_LEN] {
+    let mut record = [0u8; CALIBRATION_LEN];
+    record[0] = CALIBRATION_VERSION;
+    for (idx, value) in baseline.iter().chain(thresholds.iter()).enumerate() {
+        record[1 + idx * 2..3 + idx * 2].copy_from_slice(&value.to_be_bytes());
+    }
+    record
+}
+
+/// Decode a stored calibration into the baseline and thresholds of each pin, `None` if it is stale or invalid
+fn decode_calibration(record: &[u8]) -> Option<([u16; PIN_COUNT], [u16; PIN_COUNT])> {
+    if record.len() != CALIBRATION_LEN || record[0] != CALIBRATION_VERSION {
+        return None;
+    }
+    let mut values = [0u16; 2 * PIN_COUNT];
+    for (idx, value) in values.iter_mut().enumerate() {
+        *value = u16::from_be_bytes([record[1 + idx * 2], record[2 + idx * 2]]);
+    }
+    let mut baseline = [0u16; PIN_COUNT];
+    let mut thresholds = [0u16; PIN_COUNT];
+    baseline.copy_from_slice(&values[..PIN_COUNT]);
+    thresholds.copy_from_slice(&values[PIN_COUNT..]);
+    if thresholds.iter().any(|&threshold| threshold == 0 || threshold > MAX_THRESHOLD) {
+        return None;
+    }
+    Some((baseline, thresholds))
+}
+
+/// The average of `samples` readings of each pin from `acquire`
+fn measure_baseline<F>(samples: u16, mut acquire: F) -> Result<[u16; PIN_COUNT], Error>
+where
+    F: FnMut(u8) -> Result<u16, Error>,
+{
+    if samples == 0 {
+        return Err(Error::CalibrationFailed);
+    }
+    let mut baseline = [0; PIN_COUNT];
+    for pin in 0..=MAX_PIN_IDX {
+        let mut total = 0u32;
+        for _ in 0..samples {
+            total += u32::from(acquire(pin)?);
+        }
+        baseline[pin as usize] = (total / u32::from(samples)) as u16;
+    }
+    Ok(baseline)
+}
+
+/// Touch sense hardware for testing, each pin reads the count it was last given
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MockTouchSense {
+    /// the count of each pin, `None` reaches the max count
+    pub counts: [Option<u16>; PIN_COUNT],
+    /// the pin being acquired
+    pub acquiring: Option<u8>,
+    /// whether all the pins are being acquired
+    pub grouped: bool,
+}
+
+#[cfg(test)]
+impl TouchSense for MockTouchSense {
+    fn start(&mut self, pin: u8) {
+        self.acquiring = Some(pin);
+    }
+
+    fn in_progress(&mut self) -> bool {
+        self.acquiring.is_some() || self.grouped
+    }
+
+    fn read(&mut self, pin: u8) -> Option<u16> {
+        assert_eq!(self.acquiring.take(), Some(pin));
+        self.counts[pin as usize]
+    }
+
+    fn clear(&mut self) {
+        self.acquiring = None;
+        self.grouped = false;
+    }
+
+    fn acquire(&mut self, pin: u8) -> Option<u16> {
+        self.counts[pin as usize]
+    }
+
+    fn start_all(&mut self) -> bool {
+        self.grouped = true;
+        true
+    }
+
+    fn read_all(&mut self) -> [Option<u16>; PIN_COUNT] {
+        assert!(self.grouped);
+        self.grouped = false;
+        self.counts
+    }
+}
+
+#[cfg(test)]
+impl InputManager<MockTouchSense> {
+    /// Creates an input manager for testing, driven by a `MockTouchSense` with a threshold of 1000 and no hysteresis
+    pub(crate) fn mock(debounce: u8, polarity: Polarity) -> Self {
+        let tsc = TscManager::new(MockTouchSense::default(), 1000, 0);
+        InputManager::new(tsc, debounce, polarity, LONG_PRESS_MS)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+
+    fn input_manager(debounce: u8) -> InputManager<MockTouchSense> {
+        InputManager::mock(debounce, Polarity::default())
+    }
+
+    /// Move the clock of `im` on by `ms`
+    fn advance(im: &mut InputManager<MockTouchSense>, ms: u32) {
+        let now_ms = im.now_ms.wrapping_add(ms);
+        im.tick(now_ms);
+    }
+
+    fn tsc_manager() -> TscManager<MockTouchSense> {
+        TscManager::new(MockTouchSense::default(), 1000, HYSTERESIS)
+    }
+
+    /// Set the input vector directly and return the resulting output
+    fn press(im: &mut InputManager<MockTouchSense>, vector: u8) -> Result<InputEvent, Error> {
+        im.raw_vector = vector;
+        next_output(im)
+    }
+
+    /// Complete the acquisition cycle, then return the next event
+    fn next_output(im: &mut InputManager<MockTouchSense>) -> Result<InputEvent, Error> {
+        im.complete_cycle()?;
+        im.output()
+    }
+
     #[test]
     fn triple_detected() {
-        let mut im = unsafe {
-            // safe because we dont use the hw in tests
-            InputManager::new(core::mem::uninitialized())
-        };
+        let mut im = input_manager(1);
 
         for _ in 0..3 {
             im.update_input(true)
         }
-        assert_eq!(im.output().expect("No input detected"), InputEvent::Multi);
+        assert_eq!(next_output(&mut im).expect("No input detected"), InputEvent::Multi);
     }
 
     #[test]
     fn dual_detected() {
-        let mut im = unsafe {
-            // safe because we dont use the hw in tests
-            InputManager::new(core::mem::uninitialized())
-        };
+        let mut im = input_manager(1);
 
         im.pin_idx = 0;
         im.update_input(true);
         im.pin_idx = 2;
         im.update_input(true);
-        assert_eq!(im.output().expect("No input detected"), InputEvent::Dual);
+        assert_eq!(next_output(&mut im).expect("No input detected"), InputEvent::Dual);
+    }
+
+    #[test]
+    fn single_sample_glitch_ignored() {
+        let mut im = input_manager(2);
+        // left reads active for a single cycle
+        for active in [true, false, false, false, false, false].iter() {
+            im.update_input(*active);
+            assert_eq!(next_output(&mut im), Err(Error::NoInput));
+        }
+        // then for two cycles
+        for active in [true, false, false, true, false, false].iter() {
+            im.update_input(*active);
+        }
+        assert_eq!(next_output(&mut im), Ok(InputEvent::Left));
+    }
+
+    #[test]
+    fn long_press_detected_once() {
+        let mut im = InputManager::new(tsc_manager(), 1, Polarity::default(), 500);
+        im.update_input(true);
+        assert_eq!(next_output(&mut im), Ok(InputEvent::Left));
+        for _ in 0..4 {
+            advance(&mut im, 100);
+            assert_eq!(next_output(&mut im), Err(Error::NoInput));
+        }
+        advance(&mut im, 100);
+        assert_eq!(next_output(&mut im), Ok(InputEvent::LongPressLeft));
+        for _ in 0..10 {
+            advance(&mut im, 100);
+            assert_eq!(next_output(&mut im), Err(Error::NoInput));
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reset_combo_detected_once() {
+        let mut im = input_manager(1);
+        assert_eq!(press(&mut im, ALL), Ok(InputEvent::Multi));
+        let mut events = Vec::new();
+        for _ in 0..80 {
+            advance(&mut im, 100);
+            if let Ok(event) = press(&mut im, ALL) {
+                events.push((im.now_ms, event));
+            }
+        }
+        assert_eq!(events, [(1000, InputEvent::LongPressMulti), (5000, InputEvent::ResetCombo)]);
+    }
+
+    #[test]
+    fn reset_combo_cancelled() {
+        let mut im = input_manager(1);
+        assert_eq!(press(&mut im, ALL), Ok(InputEvent::Multi));
+        advance(&mut im, 4000);
+        // partially released during the hold
+        assert_eq!(press(&mut im, LEFT_RIGHT), Ok(InputEvent::Dual));
+        assert_eq!(press(&mut im, ALL), Ok(InputEvent::Multi));
+        for _ in 0..4 {
+            advance(&mut im, 1000);
+            assert_ne!(press(&mut im, ALL), Ok(InputEvent::ResetCombo));
+        }
+        advance(&mut im, 1000);
+        assert_eq!(press(&mut im, ALL), Ok(InputEvent::ResetCombo));
+    }
+
+    #[test]
+    fn double_tap_detected() {
+        let mut im = input_manager(1);
+        assert_eq!(press(&mut im, MIDDLE), Ok(InputEvent::Middle));
+        advance(&mut im, 100);
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedMiddle));
+        advance(&mut im, 100);
+        assert_eq!(press(&mut im, MIDDLE), Ok(InputEvent::DoubleMiddle));
+        advance(&mut im, 100);
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedMiddle));
+        advance(&mut im, 100);
+        // a third tap starts again
+        assert_eq!(press(&mut im, MIDDLE), Ok(InputEvent::Middle));
+    }
+
+    #[test]
+    fn double_tap_too_slow() {
+        let mut im = input_manager(1);
+        assert_eq!(press(&mut im, MIDDLE), Ok(InputEvent::Middle));
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedMiddle));
+        advance(&mut im, DOUBLE_TAP_MS + 1);
+        assert_eq!(press(&mut im, MIDDLE), Ok(InputEvent::Middle));
+    }
+
+    #[test]
+    fn double_tap_interrupted() {
+        let mut im = input_manager(1);
+        assert_eq!(press(&mut im, MIDDLE), Ok(InputEvent::Middle));
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedMiddle));
+        assert_eq!(press(&mut im, LEFT), Ok(InputEvent::Left));
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedLeft));
+        assert_eq!(press(&mut im, MIDDLE), Ok(InputEvent::Middle));
+    }
+
+    /// Run a full acquisition cycle of all three pins, then return the output
+    fn cycle(im: &mut InputManager<MockTouchSense>, left: bool, middle: bool, right: bool) -> Result<InputEvent, Error> {
+        advance(im, 40);
+        for active in [left, middle, right].iter() {
+            im.update_input(*active);
+        }
+        next_output(im)
+    }
+
+    #[test]
+    fn swipe_detected() {
+        let mut im = input_manager(1);
+        assert_eq!(cycle(&mut im, true, false, false), Ok(InputEvent::Left));
+        assert_eq!(cycle(&mut im, true, true, false), Ok(InputEvent::LeftMiddle));
+        assert_eq!(cycle(&mut im, false, true, true), Ok(InputEvent::SwipeRight));
+        // the end of the swipe is not reported
+        assert_eq!(cycle(&mut im, false, false, true), Err(Error::NoInput));
+        assert_eq!(cycle(&mut im, false, false, false), Err(Error::NoInput));
+        assert_eq!(cycle(&mut im, false, false, true), Ok(InputEvent::Right));
+        assert_eq!(cycle(&mut im, false, false, false), Ok(InputEvent::ReleasedRight));
+
+        assert_eq!(cycle(&mut im, false, true, true), Ok(InputEvent::RightMiddle));
+        assert_eq!(cycle(&mut im, true, false, false), Ok(InputEvent::SwipeLeft));
+    }
+
+    #[test]
+    fn swipe_noise_ignored() {
+        let mut im = input_manager(1);
+        // out of order
+        assert_eq!(cycle(&mut im, true, false, false), Ok(InputEvent::Left));
+        assert_eq!(cycle(&mut im, false, false, true), Ok(InputEvent::Right));
+        assert_eq!(cycle(&mut im, false, true, false), Ok(InputEvent::Middle));
+        assert_eq!(cycle(&mut im, false, false, false), Ok(InputEvent::ReleasedMiddle));
+        // all at once
+        assert_eq!(cycle(&mut im, true, true, true), Ok(InputEvent::Multi));
+        assert_eq!(cycle(&mut im, false, false, false), Ok(InputEvent::ReleasedMulti));
+        // too slow
+        im.set_swipe(100);
+        assert_eq!(cycle(&mut im, true, false, false), Ok(InputEvent::Left));
+        assert_eq!(cycle(&mut im, false, true, false), Ok(InputEvent::Middle));
+        assert_eq!(cycle(&mut im, false, false, false), Ok(InputEvent::ReleasedMiddle));
+        assert_eq!(cycle(&mut im, false, false, true), Ok(InputEvent::Right));
+    }
+
+    #[test]
+    fn calibration_works() {
+        let mut tsc = tsc_manager();
+        tsc.set_factor(0.9);
+        let readings = [[1100, 1104], [1070, 1080], [1090, 1090]];
+        let mut sample = [0usize; 3];
+        let baseline = measure_baseline(2, |pin| {
+            let idx = pin as usize;
+            sample[idx] += 1;
+            Ok(readings[idx][sample[idx] - 1])
+        }).unwrap();
+
+        assert_eq!(baseline, [1102, 1075, 1090]);
+        assert_eq!(tsc.set_baseline(baseline), [992, 968, 981]);
+        assert_eq!(tsc.thresholds(), [992, 968, 981]);
+        assert_eq!(tsc.baseline(), [1102, 1075, 1090]);
+
+        assert_eq!(measure_baseline(0, |_| Ok(0)), Err(Error::CalibrationFailed));
+    }
+
+    #[test]
+    fn per_pin_thresholds() {
+        let mut tsc = tsc_manager();
+        tsc.tsc_threshold = [1000, 500, 1000];
+        tsc.touched = 0;
+        tsc.hysteresis = 0;
+        assert!(tsc.is_touched(0, 800, Polarity::ActiveLow));
+        assert!(!tsc.is_touched(1, 800, Polarity::ActiveLow));
+        assert!(tsc.is_touched(2, 800, Polarity::ActiveLow));
+        assert!(tsc.is_touched(1, 400, Polarity::ActiveLow));
+        assert_eq!(tsc.thresholds(), [1000, 500, 1000]);
+        assert_eq!(tsc.threshold(), 500);
+    }
+
+    #[test]
+    fn max_count_error() {
+        let mut im = input_manager(1);
+        im.tsc.tsc_threshold = [1000; PIN_COUNT];
+        im.tsc.errored = 0;
+        im.tsc.touched = 0;
+        im.tsc.hysteresis = 0;
+        im.raw_vector = MIDDLE;
+
+        im.pin_idx = 1;
+        let result = im.tsc.check_value(1, None, Polarity::ActiveLow);
+        assert_eq!(im.handle_result(result), Err(Error::AcquisitionFailed(1)));
+        assert_eq!(im.tsc.errored(), MIDDLE);
+        // the pin is treated as released, and the next pin is acquired
+        assert_eq!(im.raw_vector, NONE);
+        assert_eq!(im.pin_idx, 2);
+
+        let result = im.tsc.check_value(1, Some(1200), Polarity::ActiveLow);
+        assert_eq!(result, Ok(false));
+        assert_eq!(im.tsc.errored(), NONE);
+    }
+
+    #[test]
+    fn acquisition_timeout() {
+        let mut im = input_manager(1);
+        im.set_acquisition_timeout(100);
+        // an acquisition was started, but never completes
+        im.acquiring = true;
+        im.pin_idx = 1;
+        advance(&mut im, 100);
+        assert!(!im.acquisition_timed_out());
+        advance(&mut im, 10);
+        assert!(im.acquisition_timed_out());
+        assert_eq!(im.pin_idx, 2);
+        // only reported once
+        advance(&mut im, 200);
+        assert!(!im.acquisition_timed_out());
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut tsc = TscManager::with_thresholds(MockTouchSense::default(), [1000; PIN_COUNT], 10);
+
+        let mut presses = 0;
+        let mut releases = 0;
+        let mut touched = false;
+        // sweep down through the band and back up again, with some chatter on the way
+        let sweep = (985..=1015).rev().chain(985..=1015);
+        for value in sweep.flat_map(|value| vec![value, value + 3, value]) {
+            let now = tsc.is_touched(0, value, Polarity::ActiveLow);
+            if now && !touched {
+                presses += 1;
+            } else if !now && touched {
+                releases += 1;
+            }
+            touched = now;
+        }
+        assert_eq!(presses, 1);
+        assert_eq!(releases, 1);
+
+        // the band can be changed later
+        assert!(!tsc.is_touched(0, 995, Polarity::ActiveLow));
+        tsc.set_hysteresis(0);
+        assert!(tsc.is_touched(0, 995, Polarity::ActiveLow));
+    }
+
+    #[test]
+    fn release_detected() {
+        let mut im = input_manager(1);
+        assert_eq!(press(&mut im, NONE), Err(Error::NoInput));
+        assert_eq!(press(&mut im, LEFT), Ok(InputEvent::Left));
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedLeft));
+        assert_eq!(press(&mut im, NONE), Err(Error::NoInput));
+        assert_eq!(press(&mut im, RIGHT_MIDDLE), Ok(InputEvent::RightMiddle));
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedRightMiddle));
+    }
+
+    #[test]
+    fn input_event_is_field_less() {
+        // passed by value to applications, see `InputFn`
+        assert_eq!(core::mem::size_of::<InputEvent>(), core::mem::size_of::<i32>());
+    }
+
+    /// Poll every 40ms for `ms`, returning the interval reported at the end of each cycle and the number of acquisitions
+    fn poll(im: &mut InputManager<MockTouchSense>, ms: u32) -> (Vec<u32>, u32) {
+        let mut intervals = Vec::new();
+        let mut acquisitions = 0;
+        for _ in 0..ms / 40 {
+            advance(im, 40);
+            let cycle_end = im.pin_idx == MAX_PIN_IDX;
+            let next = im.start_new().unwrap();
+            if im.acquiring {
+                acquisitions += 1;
+                let _ = im.process_result();
+                if cycle_end {
+                    intervals.push(next);
+                }
+            }
+        }
+        (intervals, acquisitions)
+    }
+
+    #[test]
+    fn duty_cycle() {
+        let mut im = input_manager(1);
+        im.set_poll_interval(40);
+        im.set_duty_cycle(Some((1000, 400)));
+        im.tsc.sense.counts = [Some(1200); PIN_COUNT];
+
+        let (intervals, acquisitions) = poll(&mut im, 960);
+        assert!(intervals.iter().all(|&next| next == 40));
+        assert_eq!(acquisitions, 24);
+        assert!(!im.is_idle());
+
+        // idle, so cycles back off to one every 400ms
+        let (intervals, acquisitions) = poll(&mut im, 2000);
+        assert!(im.is_idle());
+        // the rest of the interval once the three pins have been acquired
+        assert_eq!(intervals, [320; 5]);
+        assert_eq!(acquisitions, 15);
+
+        // a touch returns to polling every tick
+        im.tsc.sense.counts[0] = Some(800);
+        poll(&mut im, 400);
+        assert!(!im.is_idle());
+        let (intervals, acquisitions) = poll(&mut im, 480);
+        assert!(intervals.iter().all(|&next| next == 40));
+        assert_eq!(acquisitions, 12);
+    }
+
+    #[test]
+    fn last_event_time() {
+        let mut im = input_manager(1);
+        assert_eq!(im.last_event_ms(), 0);
+        advance(&mut im, 1200);
+        assert_eq!(press(&mut im, LEFT), Ok(InputEvent::Left));
+        assert_eq!(im.last_event_ms(), 1200);
+        // no event, no interaction
+        advance(&mut im, 300);
+        assert_eq!(press(&mut im, LEFT), Err(Error::NoInput));
+        assert_eq!(im.last_event_ms(), 1200);
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedLeft));
+        assert_eq!(im.last_event_ms(), 1500);
+    }
+
+    #[test]
+    fn events_are_queued() {
+        let mut im = input_manager(1);
+        im.raw_vector = LEFT;
+        im.complete_cycle().unwrap();
+        im.raw_vector = LEFT_RIGHT;
+        im.complete_cycle().unwrap();
+        im.raw_vector = LEFT_RIGHT;
+        im.complete_cycle().unwrap();
+
+        assert_eq!(im.output(), Ok(InputEvent::Left));
+        assert_eq!(im.output(), Ok(InputEvent::Dual));
+        assert_eq!(im.output(), Err(Error::NoInput));
+    }
+
+    #[test]
+    fn sample_count() {
+        let mut low = tsc_manager();
+        let mut high = tsc_manager();
+        assert_eq!(low.set_samples(2), Ok(()));
+        assert_eq!(high.set_samples(MAX_SAMPLES), Ok(()));
+        assert_eq!(low.samples(), 2);
+        assert_eq!(high.samples(), MAX_SAMPLES);
+
+        assert_eq!(low.set_samples(0), Err(Error::InvalidSampleCount(0)));
+        assert_eq!(high.set_samples(MAX_SAMPLES + 1), Err(Error::InvalidSampleCount(MAX_SAMPLES + 1)));
+        assert_eq!(high.samples(), MAX_SAMPLES);
+
+        let mut acquisitions = 0;
+        measure_baseline(low.samples(), |_| { acquisitions += 1; Ok(1000) }).unwrap();
+        assert_eq!(acquisitions, 2 * PIN_COUNT);
+    }
+
+    #[test]
+    fn last_raw_readings() {
+        let mut im = input_manager(1);
+        im.tsc.tsc_threshold = [1000; PIN_COUNT];
+        im.tsc.hysteresis = 0;
+        im.tsc.touched = 0;
+        im.tsc.last_raw = [0; PIN_COUNT];
+
+        assert_eq!(im.tsc.check_value(0, Some(1100), Polarity::ActiveLow), Ok(false));
+        assert_eq!(im.tsc.check_value(1, Some(900), Polarity::ActiveLow), Ok(true));
+        assert_eq!(im.tsc.check_value(2, Some(1050), Polarity::ActiveLow), Ok(false));
+        assert_eq!(im.last_raw(), [1100, 900, 1050]);
+        // a failed acquisition keeps the last good reading
+        assert!(im.tsc.check_value(2, None, Polarity::ActiveLow).is_err());
+        assert_eq!(im.last_raw(), [1100, 900, 1050]);
+    }
+
+    #[test]
+    fn held_input_repeats() {
+        let mut im = input_manager(1);
+        im.set_long_press(u32::max_value());
+        im.set_repeat(Some((300, 100)));
+        assert_eq!(press(&mut im, RIGHT), Ok(InputEvent::Right));
+        let mut repeats = 0;
+        for _ in 0..25 {
+            advance(&mut im, 40);
+            if let Ok(event) = press(&mut im, RIGHT) {
+                assert_eq!(event, InputEvent::Right);
+                repeats += 1;
+            }
+        }
+        // at 300, 400, 500, 600, 700, 800, 900 and 1000ms
+        assert_eq!(repeats, 8);
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedRight));
+
+        // multiple inputs do not repeat
+        assert_eq!(press(&mut im, LEFT_RIGHT), Ok(InputEvent::Dual));
+        for _ in 0..25 {
+            advance(&mut im, 40);
+            assert_eq!(press(&mut im, LEFT_RIGHT), Err(Error::NoInput));
+        }
+    }
+
+    #[test]
+    fn inverted_polarity() {
+        let mut im = InputManager::mock(1, Polarity::ActiveHigh);
+        im.tsc.tsc_threshold = [1000; PIN_COUNT];
+        im.tsc.errored = 0;
+        im.tsc.touched = 0;
+        im.tsc.hysteresis = 0;
+        let polarity = im.polarity;
+        assert_eq!(im.tsc.check_value(0, Some(800), polarity), Ok(false));
+        assert_eq!(im.tsc.check_value(1, Some(1200), polarity), Ok(true));
+
+        // the same readings flip with the default polarity
+        im.tsc.touched = 0;
+        assert_eq!(im.tsc.check_value(0, Some(800), Polarity::default()), Ok(true));
+        assert_eq!(im.tsc.check_value(1, Some(1200), Polarity::default()), Ok(false));
+    }
+
+    /// Acquire each pin in turn from the mock, reading `counts`, then return the next event
+    fn acquire_cycle(im: &mut InputManager<MockTouchSense>, counts: [Option<u16>; PIN_COUNT]) -> Result<InputEvent, Error> {
+        im.tsc.sense.counts = counts;
+        for pin in 0..=MAX_PIN_IDX {
+            im.start_new()?;
+            let result = im.process_result();
+            if pin < MAX_PIN_IDX {
+                assert_eq!(result, Err(Error::Incomplete));
+            }
+        }
+        im.output()
+    }
+
+    #[test]
+    fn mock_acquisition() {
+        let mut im = input_manager(1);
+        assert_eq!(acquire_cycle(&mut im, [Some(1200); PIN_COUNT]), Err(Error::NoInput));
+        assert_eq!(acquire_cycle(&mut im, [Some(800), Some(1200), Some(1200)]), Ok(InputEvent::Left));
+        assert_eq!(acquire_cycle(&mut im, [Some(800), Some(1200), Some(800)]), Ok(InputEvent::Dual));
+        assert_eq!(acquire_cycle(&mut im, [Some(1200); PIN_COUNT]), Ok(InputEvent::ReleasedDual));
+        assert_eq!(im.last_raw(), [1200; PIN_COUNT]);
+
+        // only one acquisition at a time
+        im.start_new().unwrap();
+        assert_eq!(im.start_new(), Err(Error::AcquisitionInProgress));
+        im.tsc.abort();
+
+        // a failed pin is released and the cycle moves on
+        im.pin_idx = 0;
+        im.tsc.sense.counts = [Some(1200), None, Some(1200)];
+        im.start_new().unwrap();
+        assert_eq!(im.process_result(), Err(Error::Incomplete));
+        im.start_new().unwrap();
+        assert_eq!(im.process_result(), Err(Error::AcquisitionFailed(1)));
+        assert_eq!(im.tsc.errored(), MIDDLE);
+        assert_eq!(im.pin_idx, 2);
+    }
+
+    #[derive(Default)]
+    struct MockStorage {
+        record: Option<std::vec::Vec<u8>>,
+        saves: usize,
+    }
+
+    impl Storage for MockStorage {
+        fn load(&mut self, buf: &mut [u8]) -> Option<usize> {
+            let record = self.record.as_ref()?;
+            buf[..record.len()].copy_from_slice(record);
+            Some(record.len())
+        }
+
+        fn save(&mut self, data: &[u8]) -> Result<(), Error> {
+            self.record = Some(data.to_vec());
+            self.saves += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn calibration_restored() {
+        let mut storage = MockStorage::default();
+        storage.record = Some(encode_calibration([1100, 1070, 1090], [1080, 1050, 1070]).to_vec());
+        let mut tsc = tsc_manager();
+        // a calibration would fail, so must not be attempted
+        tsc.sense.counts = [None; PIN_COUNT];
+        let im = InputManager::with_storage(tsc, 1, Polarity::default(), LONG_PRESS_MS, &mut storage).unwrap();
+        assert_eq!(im.thresholds(), [1080, 1050, 1070]);
+        assert_eq!(im.tsc.baseline(), [1100, 1070, 1090]);
+        assert_eq!(storage.saves, 0);
+    }
+
+    #[test]
+    fn calibration_saved() {
+        let mut storage = MockStorage::default();
+        let mut tsc = tsc_manager();
+        tsc.sense.counts = [Some(1100), Some(1070), Some(1090)];
+        assert_eq!(tsc.restore(&mut storage), Ok([1078, 1049, 1068]));
+        assert_eq!(storage.saves, 1);
+
+        // a stale calibration is replaced
+        storage.record.as_mut().unwrap()[0] = CALIBRATION_VERSION + 1;
+        let mut tsc = tsc_manager();
+        tsc.sense.counts = [Some(1200); PIN_COUNT];
+        assert_eq!(tsc.restore(&mut storage), Ok([1176; PIN_COUNT]));
+        assert_eq!(storage.saves, 2);
+
+        let mut tsc = tsc_manager();
+        tsc.sense.counts = [None; PIN_COUNT];
+        assert_eq!(tsc.restore(&mut storage), Ok([1176; PIN_COUNT]));
+        assert_eq!(decode_calibration(&[CALIBRATION_VERSION; 3]), None);
+    }
+
+    #[test]
+    fn grouped_acquisition() {
+        let inputs = [
+            [Some(800), Some(1200), Some(1200)],
+            [Some(800), Some(1200), Some(800)],
+            [Some(1200), Some(800), Some(1200)],
+            [Some(1200); PIN_COUNT],
+        ];
+        let mut sequential = input_manager(1);
+        let mut grouped = input_manager(1);
+        grouped.set_acquisition_mode(AcquisitionMode::Grouped);
+        for counts in inputs.iter() {
+            let event = acquire_cycle(&mut sequential, *counts);
+            grouped.tsc.sense.counts = *counts;
+            grouped.start_new().unwrap();
+            assert_eq!(grouped.process_result(), Ok(()));
+            assert_eq!(grouped.raw_vector, sequential.raw_vector);
+            assert_eq!(grouped.output(), event);
+            assert_eq!(grouped.pin_idx, 0);
+        }
+
+        // a failed pin is reported once the other pins have been read
+        grouped.tsc.sense.counts = [Some(800), None, Some(1200)];
+        grouped.start_new().unwrap();
+        assert_eq!(grouped.process_result(), Err(Error::AcquisitionFailed(1)));
+        assert_eq!(grouped.raw_vector, LEFT);
+        assert_eq!(grouped.pin_idx, 0);
+    }
+
+    #[test]
+    fn max_count_error_scan_continues() {
+        let mut im = input_manager(1);
+        assert_eq!(acquire_cycle(&mut im, [Some(1200), Some(800), Some(1200)]), Ok(InputEvent::Middle));
+
+        // the middle pad gets stuck, it is released and the other pins are still scanned
+        im.tsc.sense.counts = [Some(800), None, Some(1200)];
+        im.start_new().unwrap();
+        assert_eq!(im.process_result(), Err(Error::Incomplete));
+        im.start_new().unwrap();
+        assert_eq!(im.process_result(), Err(Error::AcquisitionFailed(1)));
+        assert_eq!(im.pin_idx, 2);
+        im.start_new().unwrap();
+        assert_eq!(im.process_result(), Ok(()));
+        assert_eq!(im.raw_vector, LEFT);
+        assert_eq!(im.output(), Ok(InputEvent::Left));
+
+        // a failure on the last pin still completes the cycle
+        im.tsc.sense.counts = [Some(1200), Some(1200), None];
+        for _ in 0..MAX_PIN_IDX {
+            im.start_new().unwrap();
+            assert_eq!(im.process_result(), Err(Error::Incomplete));
+        }
+        im.start_new().unwrap();
+        assert_eq!(im.process_result(), Err(Error::AcquisitionFailed(2)));
+        assert_eq!(im.output(), Ok(InputEvent::ReleasedLeft));
+        assert_eq!(im.pin_idx, 0);
+
+        // every pin failing is reported once the queue is drained
+        im.tsc.sense.counts = [None; PIN_COUNT];
+        for pin in 0..=MAX_PIN_IDX {
+            im.start_new().unwrap();
+            assert_eq!(im.process_result(), Err(Error::AcquisitionFailed(pin)));
+        }
+        assert_eq!(im.output(), Err(Error::AllAcquisitionsFailed));
+
+        // and recovers with the pads
+        assert_eq!(acquire_cycle(&mut im, [Some(1200); PIN_COUNT]), Err(Error::NoInput));
+    }
+
+    #[test]
+    fn mock_calibration() {
+        let mut im = input_manager(1);
+        im.tsc.sense.counts = [Some(1100), Some(1070), Some(1090)];
+        assert_eq!(im.calibrate(SAMPLES), Ok([1078, 1049, 1068]));
+        assert_eq!(im.tsc.baseline(), [1100, 1070, 1090]);
+
+        // the factor is configurable
+        im.set_factor(0.5);
+        assert_eq!(im.calibrate(1), Ok([550, 535, 545]));
+        im.set_factor(CALIBRATION_FACTOR);
+
+        im.tsc.sense.counts[2] = None;
+        assert_eq!(im.calibrate(SAMPLES), Err(Error::CalibrationFailed));
+        assert_eq!(im.calibrate(0), Err(Error::InvalidSampleCount(0)));
+        assert_eq!(im.calibrate(MAX_SAMPLES + 1), Err(Error::InvalidSampleCount(MAX_SAMPLES + 1)));
+
+        // not while an acquisition is running
+        im.tsc.sense.counts = [Some(1020); PIN_COUNT];
+        im.start_new().unwrap();
+        assert_eq!(im.calibrate(SAMPLES), Err(Error::AcquisitionInProgress));
+        assert_eq!(im.thresholds(), [550, 535, 545]);
+        im.process_result().unwrap_err();
+        assert_eq!(im.calibrate(SAMPLES), Ok([1000; PIN_COUNT]));
+    }
+
+    #[test]
+    fn sampled_calibration() {
+        let mut im = input_manager(1);
+        assert_eq!(im.start_calibration(0), Err(Error::InvalidSampleCount(0)));
+        im.start_calibration(2).unwrap();
+        assert!(im.is_calibrating());
+
+        // the readings would be a touch, but only calibrate
+        assert_eq!(acquire_cycle(&mut im, [Some(800), Some(900), Some(700)]), Err(Error::NoInput));
+        assert_eq!(im.take_calibration(), None);
+        assert_eq!(acquire_cycle(&mut im, [Some(1000), Some(1100), Some(900)]), Err(Error::NoInput));
+        assert!(!im.is_calibrating());
+        assert_eq!(im.take_calibration(), Some(Ok([882, 980, 784])));
+        assert_eq!(im.take_calibration(), None);
+        assert_eq!(im.tsc.baseline(), [900, 1000, 800]);
+
+        // sampled from grouped acquisitions too
+        im.set_acquisition_mode(AcquisitionMode::Grouped);
+        im.start_calibration(1).unwrap();
+        im.tsc.sense.counts = [Some(1000); PIN_COUNT];
+        im.start_new().unwrap();
+        assert_eq!(im.process_result(), Ok(()));
+        assert_eq!(im.take_calibration(), Some(Ok([980; PIN_COUNT])));
+
+        // a failed acquisition fails the calibration, leaving the thresholds
+        im.start_calibration(1).unwrap();
+        im.tsc.sense.counts[1] = None;
+        im.start_new().unwrap();
+        assert_eq!(im.process_result(), Ok(()));
+        assert_eq!(im.take_calibration(), Some(Err(Error::CalibrationFailed)));
+        assert_eq!(im.thresholds(), [980; PIN_COUNT]);
+    }
+
+    #[test]
+    fn custom_mapping() {
+        let mut im = input_manager(1);
+        let mut mapping = DEFAULT_MAPPING;
+        mapping[LEFT_MIDDLE as usize] = InputEvent::Middle;
+        mapping[RIGHT as usize] = InputEvent::Left;
+        im.set_mapping(mapping);
+        assert_eq!(press(&mut im, LEFT_MIDDLE), Ok(InputEvent::Middle));
+        assert_eq!(press(&mut im, RIGHT), Ok(InputEvent::Left));
+        assert_eq!(press(&mut im, LEFT_RIGHT), Ok(InputEvent::Dual));
+        assert_eq!(press(&mut im, NONE), Ok(InputEvent::ReleasedDual));
+    }
+}
@@ -4,4 +4,6 @@ pub mod bms;
 pub mod notification;
 pub mod syscall;
 pub mod types;
+pub mod alarm;
+pub mod haptic;
 
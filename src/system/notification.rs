@@ -3,25 +3,52 @@
 //! Push notification parsing
 
 use crate::ingress::buffer::Buffer;
+use heapless::consts::*;
+use heapless::{String, Vec};
 
 pub const BUFF_SIZE: usize = 512;
 pub const BUFF_COUNT: usize = 4;
+/// The maximum number of sections in a notification
+pub const MAX_SECTIONS: usize = 8;
+/// The priority of notifications that do not specify one
+pub const DEFAULT_PRIORITY: u8 = 0;
+/// The highest priority a notification can have
+pub const MAX_PRIORITY: u8 = 9;
 
 #[derive(Copy, Clone)]
 pub struct Notification {
-    section_indexes: [usize; 3],
+    /// assigned by the `NotificationManager` when the notification is added
+    id: u32,
+    section_indexes: [usize; MAX_SECTIONS],
+    section_count: usize,
     inner: Buffer,
+    priority: u8,
+    read: bool,
+    /// the time the notification was last received
+    received_ms: u32,
+    /// the unix time the notification was sent, in seconds
+    timestamp: u32,
+    /// the number of times the notification was received, see `NotificationManager::set_dedup`
+    count: u16,
 }
 
 impl Notification {
     pub const fn default() -> Notification {
         Notification {
-            section_indexes: [0usize; 3],
+            id: 0,
+            section_indexes: [0usize; MAX_SECTIONS],
+            section_count: 0,
             inner: Buffer {
                 btype: crate::ingress::buffer::Type::Unknown,
                 payload: [0u8; BUFF_SIZE],
                 payload_idx: 0,
+                truncated: false,
             },
+            priority: DEFAULT_PRIORITY,
+            read: false,
+            received_ms: 0,
+            timestamp: 0,
+            count: 1,
         }
     }
 
@@ -29,70 +56,647 @@ impl Notification {
         &self.inner.payload[..self.inner.payload_idx]
     }
 
-    pub fn from_buffer(buffer: &Buffer, idxs: &[usize; 3]) -> Result<Notification, NotificationError> {
+    /// Creates a notification from a buffer, `idxs` being the end index of each section
+    pub fn from_buffer(buffer: &Buffer, idxs: &[usize]) -> Result<Notification, NotificationError> {
+        if idxs.len() > MAX_SECTIONS {
+            return Err(NotificationError::Parsing);
+        }
+        let mut section_indexes = [0usize; MAX_SECTIONS];
+        section_indexes[..idxs.len()].copy_from_slice(idxs);
         Ok(Notification {
-            section_indexes: idxs.clone(),
-            inner: buffer.clone()
+            id: 0,
+            section_indexes,
+            section_count: idxs.len(),
+            inner: buffer.clone(),
+            priority: DEFAULT_PRIORITY,
+            read: false,
+            received_ms: 0,
+            timestamp: 0,
+            count: 1,
         })
     }
 
+    /// The id the notification was given when it was added, see `NotificationManager::remove`
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Whether the notification was clipped to fit in the buffer
+    pub fn is_truncated(&self) -> bool {
+        self.inner.is_truncated()
+    }
+
+    /// The priority of the notification, `MAX_PRIORITY` being the most important
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Whether the notification has been read
+    pub fn is_read(&self) -> bool {
+        self.read
+    }
+
+    /// The number of times the notification was received
+    pub fn count(&self) -> u16 {
+        self.count
+    }
+
+    /// The time the notification was last received, see `NotificationManager::tick`
+    pub fn received_ms(&self) -> u32 {
+        self.received_ms
+    }
+
+    /// The unix time the notification was sent in seconds, or when it arrived if the sender did not say
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// Whether the notification has the same source, title and body as `other`
+    fn same_content(&self, other: &Notification) -> bool {
+        self.source() == other.source() && self.title() == other.title() && self.body() == other.body()
+    }
+
+    /// The number of sections in the notification
+    pub fn section_count(&self) -> usize {
+        self.section_count
+    }
+
+    /// Returns the section at `index`, if it exists
+    pub fn section(&self, index: usize) -> Option<&str> {
+        let section = self.inner.sections(&self.section_indexes[..self.section_count]).nth(index)?;
+        // the sections are validated as UTF-8 by the ingress manager
+        Some(unsafe { core::str::from_utf8_unchecked(section) })
+    }
+
     pub fn source(&self) -> &str {
-        unsafe { core::str::from_utf8_unchecked(&self.inner.payload[0..self.section_indexes[1]]) }
+        self.section(0).unwrap_or("")
     }
 
     pub fn title(&self) -> &str {
-        unsafe { core::str::from_utf8_unchecked(&self.inner.payload[self.section_indexes[0]..self.section_indexes[1]]) }
+        self.section(1).unwrap_or("")
     }
 
     pub fn body(&self) -> &str {
-        unsafe { core::str::from_utf8_unchecked(&self.inner.payload[self.section_indexes[1]..self.section_indexes[2]]) }
+        self.section(2).unwrap_or("")
+    }
+}
+
+/// An owned copy of a notification, which can be serialized for hosts
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NotificationRecord {
+    pub source: alloc::string::String,
+    pub title: alloc::string::String,
+    pub body: alloc::string::String,
+    pub priority: u8,
+    pub read: bool,
+    pub received_ms: u32,
+    pub timestamp: u32,
+    pub count: u16,
+    pub truncated: bool,
+}
+
+#[cfg(feature = "serde")]
+impl From<&Notification> for NotificationRecord {
+    fn from(notification: &Notification) -> Self {
+        use alloc::string::ToString;
+        NotificationRecord {
+            source: notification.source().to_string(),
+            title: notification.title().to_string(),
+            body: notification.body().to_string(),
+            priority: notification.priority,
+            read: notification.read,
+            received_ms: notification.received_ms,
+            timestamp: notification.timestamp,
+            count: notification.count,
+            truncated: notification.is_truncated(),
+        }
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum NotificationError {
     Parsing,
+    /// The store is full and the policy is `FullPolicy::RejectNewest`
+    Full,
+    /// The source of the notification is muted
+    Muted,
+    /// The muted source list is full, or the source is too long to mute
+    MuteListFull,
+    /// There is no notification with the id
+    NotFound,
 }
 
+/// A notification added to the `NotificationManager`
+#[derive(Copy, Clone)]
+pub struct Added {
+    /// The id of the notification, or of the notification it was a duplicate of
+    pub id: u32,
+    /// The oldest notification, if it was evicted to make room
+    pub evicted: Option<Notification>,
+}
+
+/// What to do when a notification is added to a full store
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FullPolicy {
+    /// Drop the oldest notification to make room for the new one
+    EvictOldest,
+    /// Reject the new notification
+    RejectNewest,
+}
+
+/// A ring of notifications, oldest first
 pub struct NotificationManager {
     pool: [Notification; BUFF_COUNT],
-    idx: usize,
+    /// the pool index of the oldest notification
+    head: usize,
+    len: usize,
+    policy: FullPolicy,
+    muted: Vec<String<U32>, U8>,
+    /// the number of notifications rejected because their source was muted
+    muted_count: u32,
+    now_ms: u32,
+    dedup_ms: Option<u32>,
+    ttl_ms: Option<u32>,
+    next_id: u32,
 }
 
 impl NotificationManager {
     pub fn new() -> NotificationManager {
         NotificationManager {
             pool: [Notification::default(); BUFF_COUNT],
-            idx: 0,
+            head: 0,
+            len: 0,
+            policy: FullPolicy::EvictOldest,
+            muted: Vec::new(),
+            muted_count: 0,
+            now_ms: 0,
+            dedup_ms: None,
+            ttl_ms: None,
+            next_id: 0,
         }
     }
 
-    /// takes a closure to execute on the buffer
+    /// An owned copy of the notification at `index`, oldest first
+    #[cfg(feature = "serde")]
+    pub fn get(&mut self, index: usize) -> Option<NotificationRecord> {
+        let mut record = None;
+        self.peek_notification(index, |notification| record = Some(NotificationRecord::from(notification)));
+        record
+    }
+
+    /// takes a closure to execute on the notification at `index`, oldest first
     pub fn peek_notification<F>(&mut self, index: usize, f: F)
     where
         F: FnOnce(&Notification),
     {
-        let notification = &self.pool[index];
-        f(&notification);
+        if index < self.len {
+            let notification = &self.pool[self.slot(index)];
+            f(&notification);
+        }
     }
 
+    /// takes a closure to execute on the notification at `rank`, highest priority first,
+    /// notifications of the same priority are ordered newest first
+    pub fn peek_by_priority<F>(&mut self, rank: usize, f: F)
+    where
+        F: FnOnce(&Notification),
+    {
+        if let Some(index) = self.priority_index(rank) {
+            self.peek_notification(index, f);
+        }
+    }
+
+    /// The index of the notification at `rank`, see `peek_by_priority`
+    pub fn priority_index(&self, rank: usize) -> Option<usize> {
+        if rank < self.len {
+            Some(self.priority_order()[rank])
+        } else {
+            None
+        }
+    }
+
+    /// The index of each notification, highest priority first
+    fn priority_order(&self) -> [usize; BUFF_COUNT] {
+        let mut order = [0usize; BUFF_COUNT];
+        for (rank, index) in (0..self.len).rev().enumerate() {
+            order[rank] = index;
+        }
+        let order_slice = &mut order[..self.len];
+        // stable, so newer notifications stay ahead of older ones of the same priority
+        for i in 1..order_slice.len() {
+            let mut j = i;
+            while j > 0 && self.pool[self.slot(order_slice[j])].priority > self.pool[self.slot(order_slice[j - 1])].priority {
+                order_slice.swap(j, j - 1);
+                j -= 1;
+            }
+        }
+        order
+    }
+
+    /// The number of notifications stored
     pub fn idx(&self) -> usize {
-        self.idx
+        self.len
+    }
+
+    /// Remove every notification
+    pub fn clear_all(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Remove the notification with `id`, i.e once it has been dismissed on the phone
+    pub fn remove(&mut self, id: u32) -> Result<(), NotificationError> {
+        let len = self.len;
+        self.retain(|notification| notification.id != id);
+        if self.len == len {
+            Err(NotificationError::NotFound)
+        } else {
+            Ok(())
+        }
     }
 
-    // Parses a buffer for notification info, copying into the pool
-    pub fn add(&mut self, buffer: &Buffer, idxs: &[usize; 3]) -> Result<(), NotificationError> {
-        self.pool[self.idx] = Notification::from_buffer(buffer, idxs)?;
+    /// Remove every notification from `source`
+    pub fn clear_source(&mut self, source: &str) {
+        self.retain(|notification| notification.source() != source);
+    }
 
-        self.idx += 1;
-        if self.idx + 1 > self.pool.len() {
-            // TODO impl a cirucular buffer that track head and tail
-            /* buffer is full, wrap around */
+    /// Sets how long notifications are kept before `sweep` removes them. Disabled by default, pass `None` to disable.
+    pub fn set_ttl(&mut self, ttl_ms: Option<u32>) {
+        self.ttl_ms = ttl_ms;
+    }
 
-            self.idx = 0;
+    /// Update the managers notion of time, removing notifications older than the ttl
+    pub fn sweep(&mut self, now_ms: u32) {
+        self.tick(now_ms);
+        if let Some(ttl) = self.ttl_ms {
+            self.retain(|notification| now_ms.wrapping_sub(notification.received_ms) < ttl);
+        }
+    }
+
+    /// Keep only the notifications `f` returns true for, preserving their order
+    fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Notification) -> bool,
+    {
+        let mut kept = 0;
+        for index in 0..self.len {
+            let slot = self.slot(index);
+            if f(&self.pool[slot]) {
+                let to = self.slot(kept);
+                self.pool[to] = self.pool[slot];
+                kept += 1;
+            }
         }
-        Ok(())
+        self.len = kept;
+    }
+
+    /// Update the managers notion of time
+    pub fn tick(&mut self, now_ms: u32) {
+        self.now_ms = now_ms;
+    }
+
+    /// A notification identical to one received within `window_ms` increments the existing notifications count
+    /// instead of being stored again. Disabled by default, pass `None` to disable.
+    pub fn set_dedup(&mut self, window_ms: Option<u32>) {
+        self.dedup_ms = window_ms;
+    }
+
+    /// Mark the notification at `index` as read
+    pub fn mark_read(&mut self, index: usize) {
+        if index < self.len {
+            let slot = self.slot(index);
+            self.pool[slot].read = true;
+        }
+    }
+
+    /// Mark every notification as read
+    pub fn mark_all_read(&mut self) {
+        for index in 0..self.len {
+            let slot = self.slot(index);
+            self.pool[slot].read = true;
+        }
+    }
+
+    /// The number of notifications that have not been read
+    pub fn unread_count(&self) -> usize {
+        (0..self.len).filter(|&index| !self.pool[self.slot(index)].read).count()
+    }
+
+    /// Sets what happens when a notification is added to a full store
+    pub fn set_full_policy(&mut self, policy: FullPolicy) {
+        self.policy = policy;
+    }
+
+    /// Reject any further notifications from `source`
+    pub fn mute_source(&mut self, source: &str) -> Result<(), NotificationError> {
+        if self.is_muted(source) {
+            return Ok(());
+        }
+        let mut muted = String::new();
+        muted.push_str(source).map_err(|_| NotificationError::MuteListFull)?;
+        self.muted.push(muted).map_err(|_| NotificationError::MuteListFull)
+    }
+
+    /// Accept notifications from `source` again
+    pub fn unmute_source(&mut self, source: &str) {
+        if let Some(idx) = self.muted.iter().position(|muted| muted.as_str() == source) {
+            self.muted.swap_remove(idx);
+        }
+    }
+
+    /// Whether notifications from `source` are rejected
+    pub fn is_muted(&self, source: &str) -> bool {
+        self.muted.iter().any(|muted| muted.as_str() == source)
+    }
+
+    /// The number of notifications rejected because their source was muted
+    pub fn muted_count(&self) -> u32 {
+        self.muted_count
+    }
+
+    // Parses a buffer for notification info, copying into the pool.
+    // Returns the id given to the notification. When the store is full the oldest notification is evicted and returned,
+    // or the new one rejected, depending on the `FullPolicy`
+    pub fn add(&mut self, buffer: &Buffer, idxs: &[usize], priority: u8, timestamp: u32) -> Result<Added, NotificationError> {
+        let mut notification = Notification::from_buffer(buffer, idxs)?;
+        notification.priority = priority.min(MAX_PRIORITY);
+        notification.received_ms = self.now_ms;
+        notification.timestamp = timestamp;
+        if self.is_muted(notification.source()) {
+            self.muted_count += 1;
+            return Err(NotificationError::Muted);
+        }
+        if let Some(window) = self.dedup_ms {
+            let now = self.now_ms;
+            for index in (0..self.len).rev() {
+                let slot = self.slot(index);
+                let existing = &mut self.pool[slot];
+                if now.wrapping_sub(existing.received_ms) <= window && existing.same_content(&notification) {
+                    existing.count = existing.count.saturating_add(1);
+                    existing.received_ms = now;
+                    existing.timestamp = notification.timestamp;
+                    existing.read = false;
+                    existing.priority = existing.priority.max(notification.priority);
+                    return Ok(Added { id: existing.id, evicted: None });
+                }
+            }
+        }
+        let evicted = if self.len == self.pool.len() {
+            if self.policy == FullPolicy::RejectNewest {
+                return Err(NotificationError::Full);
+            }
+            let oldest = self.pool[self.head];
+            self.head = (self.head + 1) % self.pool.len();
+            self.len -= 1;
+            Some(oldest)
+        } else {
+            None
+        };
+        notification.id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let slot = self.slot(self.len);
+        self.pool[slot] = notification;
+        self.len += 1;
+        Ok(Added { id: notification.id, evicted })
+    }
+
+    /// The pool index of the notification at `index`, oldest first
+    fn slot(&self, index: usize) -> usize {
+        (self.head + index) % self.pool.len()
     }
 }
 
-// TODO testing of parsing
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ingress::buffer::Type;
+    use std::string::String;
+    use std::vec::Vec;
+
+    /// Adds a notification with a single section containing `title`
+    fn add(nm: &mut NotificationManager, title: &str) -> Result<Option<Notification>, NotificationError> {
+        add_with_priority(nm, title, DEFAULT_PRIORITY)
+    }
+
+    fn add_with_priority(nm: &mut NotificationManager, title: &str, priority: u8) -> Result<Option<Notification>, NotificationError> {
+        add_from(nm, "", title, priority)
+    }
+
+    fn add_from(nm: &mut NotificationManager, source: &str, title: &str, priority: u8) -> Result<Option<Notification>, NotificationError> {
+        let mut buffer = Buffer::default();
+        buffer.btype = Type::Notification;
+        for byte in source.bytes() {
+            buffer.write(byte).unwrap();
+        }
+        let source_end = buffer.payload_idx;
+        for byte in title.bytes() {
+            buffer.write(byte).unwrap();
+        }
+        nm.add(&buffer, &[source_end, buffer.payload_idx], priority, 0).map(|added| added.evicted)
+    }
+
+    fn titles(nm: &mut NotificationManager) -> Vec<String> {
+        let mut titles = Vec::new();
+        for idx in 0..nm.idx() {
+            nm.peek_notification(idx, |n| titles.push(String::from(n.title())));
+        }
+        titles
+    }
+
+    #[test]
+    fn oldest_evicted_when_full() {
+        let mut nm = NotificationManager::new();
+        for idx in 0..BUFF_COUNT {
+            assert!(add(&mut nm, &format!("{}", idx)).unwrap().is_none());
+        }
+        let evicted = add(&mut nm, "newest").unwrap().unwrap();
+        assert_eq!(evicted.title(), "0");
+        assert_eq!(nm.idx(), BUFF_COUNT);
+        assert_eq!(titles(&mut nm), ["1", "2", "3", "newest"]);
+    }
+
+    #[test]
+    fn newest_rejected_when_full() {
+        let mut nm = NotificationManager::new();
+        nm.set_full_policy(FullPolicy::RejectNewest);
+        for idx in 0..BUFF_COUNT {
+            add(&mut nm, &format!("{}", idx)).unwrap();
+        }
+        assert_eq!(add(&mut nm, "newest").err(), Some(NotificationError::Full));
+        assert_eq!(titles(&mut nm), ["0", "1", "2", "3"]);
+    }
+
+    #[test]
+    fn priority_order() {
+        let mut nm = NotificationManager::new();
+        add_with_priority(&mut nm, "email", 1).unwrap();
+        add_with_priority(&mut nm, "call", 9).unwrap();
+        add_with_priority(&mut nm, "message", 1).unwrap();
+        add_with_priority(&mut nm, "alarm", 12).unwrap();
+        let mut titles = Vec::new();
+        for rank in 0..nm.idx() {
+            nm.peek_by_priority(rank, |n| titles.push((String::from(n.title()), n.priority())));
+        }
+        assert_eq!(titles, [
+            (String::from("alarm"), MAX_PRIORITY),
+            (String::from("call"), 9),
+            (String::from("message"), 1),
+            (String::from("email"), 1),
+        ]);
+    }
+
+    #[test]
+    fn muted_source_rejected() {
+        let mut nm = NotificationManager::new();
+        nm.mute_source("spam").unwrap();
+        assert!(nm.is_muted("spam"));
+        assert_eq!(add_from(&mut nm, "spam", "buy now", DEFAULT_PRIORITY).err(), Some(NotificationError::Muted));
+        assert!(add_from(&mut nm, "spammer", "hello", DEFAULT_PRIORITY).unwrap().is_none());
+        assert!(add_from(&mut nm, "friend", "hello", DEFAULT_PRIORITY).unwrap().is_none());
+        assert_eq!(nm.idx(), 2);
+        assert_eq!(nm.muted_count(), 1);
+
+        nm.unmute_source("spam");
+        assert!(add_from(&mut nm, "spam", "buy now", DEFAULT_PRIORITY).unwrap().is_none());
+        assert_eq!(nm.idx(), 3);
+    }
+
+    #[test]
+    fn unread_count() {
+        let mut nm = NotificationManager::new();
+        for idx in 0..3 {
+            add(&mut nm, &format!("{}", idx)).unwrap();
+        }
+        assert_eq!(nm.unread_count(), 3);
+        nm.mark_read(1);
+        nm.mark_read(1);
+        assert_eq!(nm.unread_count(), 2);
+        nm.peek_notification(1, |n| assert!(n.is_read()));
+        nm.peek_notification(0, |n| assert!(!n.is_read()));
+        nm.mark_all_read();
+        assert_eq!(nm.unread_count(), 0);
+    }
+
+    #[test]
+    fn evicting_unread() {
+        let mut nm = NotificationManager::new();
+        for idx in 0..BUFF_COUNT {
+            add(&mut nm, &format!("{}", idx)).unwrap();
+        }
+        nm.mark_read(1);
+        assert_eq!(nm.unread_count(), BUFF_COUNT - 1);
+        // the oldest is unread, the new notification replaces it
+        assert!(!add(&mut nm, "newest").unwrap().unwrap().is_read());
+        assert_eq!(nm.unread_count(), BUFF_COUNT - 1);
+        // the read notification is evicted, leaving one more unread
+        assert!(add(&mut nm, "newer").unwrap().unwrap().is_read());
+        assert_eq!(nm.unread_count(), BUFF_COUNT);
+    }
+
+    #[test]
+    fn duplicates_counted() {
+        let mut nm = NotificationManager::new();
+        nm.set_dedup(Some(1000));
+        for _ in 0..3 {
+            nm.tick(nm.now_ms + 500);
+            add(&mut nm, "ping").unwrap();
+        }
+        assert_eq!(nm.idx(), 1);
+        nm.peek_notification(0, |n| {
+            assert_eq!(n.count(), 3);
+            assert_eq!(n.received_ms(), 1500);
+        });
+
+        // outside of the window
+        nm.tick(3000);
+        add(&mut nm, "ping").unwrap();
+        add(&mut nm, "pong").unwrap();
+        assert_eq!(nm.idx(), 3);
+
+        nm.set_dedup(None);
+        add(&mut nm, "pong").unwrap();
+        assert_eq!(nm.idx(), 4);
+    }
+
+    #[test]
+    fn remove_by_id() {
+        let mut nm = NotificationManager::new();
+        let mut buffer = Buffer::default();
+        buffer.btype = Type::Notification;
+        let first = nm.add(&buffer, &[0], DEFAULT_PRIORITY, 0).unwrap().id;
+        buffer.write(b'x').unwrap();
+        let second = nm.add(&buffer, &[1], DEFAULT_PRIORITY, 0).unwrap().id;
+        assert_ne!(first, second);
+
+        assert_eq!(nm.remove(first), Ok(()));
+        assert_eq!(nm.idx(), 1);
+        nm.peek_notification(0, |n| assert_eq!(n.id(), second));
+        assert_eq!(nm.remove(first), Err(NotificationError::NotFound));
+        assert_eq!(nm.idx(), 1);
+    }
+
+    #[test]
+    fn clear_source() {
+        let mut nm = NotificationManager::new();
+        add_from(&mut nm, "chat", "1", DEFAULT_PRIORITY).unwrap();
+        add_from(&mut nm, "mail", "2", DEFAULT_PRIORITY).unwrap();
+        add_from(&mut nm, "chat", "3", DEFAULT_PRIORITY).unwrap();
+        add_from(&mut nm, "mail", "4", DEFAULT_PRIORITY).unwrap();
+        add_from(&mut nm, "mail", "5", DEFAULT_PRIORITY).unwrap(); // wraps the ring
+        nm.clear_source("chat");
+        assert_eq!(titles(&mut nm), ["2", "4", "5"]);
+        nm.clear_all();
+        assert_eq!(nm.idx(), 0);
+        assert_eq!(nm.unread_count(), 0);
+    }
+
+    #[test]
+    fn expired_notifications_swept() {
+        let mut nm = NotificationManager::new();
+        nm.set_ttl(Some(1000));
+        add(&mut nm, "old").unwrap();
+        nm.sweep(600);
+        add(&mut nm, "new").unwrap();
+        assert_eq!(nm.unread_count(), 2);
+        nm.sweep(999);
+        assert_eq!(nm.idx(), 2);
+        nm.sweep(1000);
+        assert_eq!(titles(&mut nm), ["new"]);
+        assert_eq!(nm.unread_count(), 1);
+        nm.sweep(1600);
+        assert_eq!(nm.idx(), 0);
+        assert_eq!(nm.unread_count(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialize_parsed_notification() {
+        use crate::ingress::encode::encode_notification;
+        use crate::ingress::ingress_manager::IngressManager;
+        use crate::system::system::System;
+
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let mut out = [0u8; 64];
+        let len = encode_notification("Messages", "Alice", "Lunch?", &mut out).unwrap();
+        imgr.write(&out[..len]).unwrap();
+        imgr.process(&mut system).unwrap();
+
+        let record = system.nm().get(0).unwrap();
+        assert!(system.nm().get(1).is_none());
+        assert_eq!(serde_json::to_value(&record).unwrap(), serde_json::json!({
+            "source": "Messages",
+            "title": "Alice",
+            "body": "Lunch?",
+            "priority": 0,
+            "read": false,
+            "received_ms": 0,
+            "timestamp": 1_546_300_800,
+            "count": 1,
+            "truncated": false,
+        }));
+        let parsed: NotificationRecord = serde_json::from_str(&serde_json::to_string(&record).unwrap()).unwrap();
+        assert_eq!(parsed, record);
+    }
+}
@@ -6,15 +6,34 @@
 use crate::types::hal::datetime::{Date, Time};
 use core::str::FromStr;
 use crate::types::hal::prelude::*;
-use crate::system::system::System;
+use crate::system::system::{System, VERSION};
+use crate::system::input::MAX_THRESHOLD;
+use crate::system::haptic::Pattern;
 
 
+/// Separates the arguments of a syscall
+pub const ARG_SEPARATOR: char = ',';
+
 #[derive(Debug, Copy, Clone, PartialEq)]
-pub enum Error {
-    ParseError,
-    UnknownSyscall
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SyscallError {
+    /// The command is not a known syscall
+    UnknownCommand,
+    /// A required argument was not given
+    MissingArg,
+    /// An argument could not be parsed, or there are too many arguments
+    BadArg,
+    /// The UTC offset is not of the form `+HH:MM` or `-HH:MM`, or is out of range
+    InvalidOffset,
+    /// The argument is outside of the range the syscall accepts
+    OutOfRange,
+    /// The hardware the syscall drives is not fitted
+    Unsupported,
 }
 
+/// The largest UTC offset in use, in minutes
+pub const MAX_UTC_OFFSET: i16 = 14 * 60;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Syscall {
     /// Set the date - example: 
@@ -22,68 +41,321 @@ pub enum Syscall {
     ///  day in week, date, month, year                         
     Date(Date),
     /// Set the time - example:
-    /// "T12:21:11"
-    /// hours, minutes, seconds
+    /// "T12:21:11" or "T12:21:11+02:00"
+    /// hours, minutes, seconds, optionally followed by the UTC offset (in minutes) of the time
+    Time(Time, Option<i16>),
+    /// Get the time - example:
+    /// "t"
+    GetTime,
+    /// Remove all notifications, they have been dismissed on the phone - example:
+    /// "C"
+    ClearNotifications,
+    /// Remove the notification with the id given when it was listed, it has been dismissed on the phone - example:
+    /// "DN3"
+    DismissNotification(u32),
+    /// Set the display brightness, 0 to 100% - example:
+    /// "B80"
+    Brightness(u8),
+    /// Get the battery percentage and voltage - example:
+    /// "GB"
+    GetBattery,
+    /// Get the kernel version - example:
+    /// "V"
+    GetVersion,
+    /// Reboot the watch, optionally after a delay in milliseconds so the response can be sent first - example:
+    /// "RB" or "RB500"
+    Reboot(u16),
+    /// Set the touch threshold of all inputs, 1 to `MAX_THRESHOLD` - example:
+    /// "TH1500"
+    SetThreshold(u16),
+    /// Recalibrate the touch thresholds from the untouched readings of each input - example:
+    /// "TC"
+    Calibrate,
+    /// Vibrate for `ms`, with a pattern of 0 single, 1 double or 2 long (see `Pattern`) - example:
+    /// "VB200,1" or "VB200" for a single buzz
+    Vibrate { ms: u16, pattern: u8 },
+    /// List the notifications, the count is followed by a response for each notification, see
+    /// `SyscallResponse::Notification` - example:
+    /// "LN"
+    ListNotifications,
+    /// Get how much of the application being loaded has been received without a gap, and its checksum once
+    /// received, so an interrupted chunked transfer can be resumed from that offset - example:
+    /// "AO"
+    ApplicationOffset,
+}
+
+/// The result of executing a syscall, framed and sent back to the sender
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SyscallResponse<'a> {
+    /// The syscall was executed successfully
+    Ack,
+    /// The syscall could not be parsed or executed
+    Error(SyscallError),
+    /// The current time, in the same format `Syscall::Time` accepts
     Time(Time),
+    /// The battery percentage and voltage (in millivolts)
+    Battery { percentage: u8, mv: u16 },
+    /// The kernel version
+    Version(&'static str),
+    /// The number of notifications that will be listed
+    Notifications(usize),
+    /// A listed notification, the source and title are truncated to fit a response
+    Notification { index: usize, id: u32, received_ms: u32, source: &'a str, title: &'a str },
+    /// The id given to a notification that was just stored, not the answer to a syscall
+    Added(u32),
+    /// The offset to resume an application transfer from, and the checksum of the application being loaded
+    ApplicationOffset { offset: usize, checksum: Option<u32> },
+}
+
+impl<'a> core::fmt::Display for SyscallResponse<'a> {
+
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            SyscallResponse::Ack => write!(f, "A"),
+            SyscallResponse::Error(err) => write!(f, "E{:?}", err),
+            SyscallResponse::Time(time) => write!(f, "T{:02}:{:02}:{:02}", time.hours, time.minutes, time.seconds),
+            SyscallResponse::Battery { percentage, mv } => write!(f, "B{},{}", percentage, mv),
+            SyscallResponse::Version(version) => write!(f, "V{}", version),
+            SyscallResponse::Notifications(count) => write!(f, "L{}", count),
+            SyscallResponse::Notification { index, id, received_ms, source, title } => {
+                write!(f, "N{},{},{},{:.16},{:.32}", index, id, received_ms, source, title)
+            },
+            SyscallResponse::Added(id) => write!(f, "I{}", id),
+            SyscallResponse::ApplicationOffset { offset, checksum: Some(checksum) } => write!(f, "O{},{:08X}", offset, checksum),
+            SyscallResponse::ApplicationOffset { offset, checksum: None } => write!(f, "O{}", offset),
+        }
+    }
 }
 
 impl FromStr for Syscall {
-    type Err = Error;
+    type Err = SyscallError;
 
-    /// Converts a string to a syscall
+    /// Converts a string to a syscall, the leading letters are the command and the rest are
+    /// its arguments, separated by `ARG_SEPARATOR`
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // first charater is the type
-        let t = s.as_bytes()[0];
-        let s: &str = &s[1..]; // remove first byte after we have the type
-        match t {
-            b'D' => Ok(Syscall::Date(Syscall::date_from_str(s)?)),
-            b'T' => Ok(Syscall::Time(Syscall::time_from_str(s)?)),
-            _ => Err(Error::UnknownSyscall)
+        let idx = s.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(s.len());
+        let (command, args) = s.split_at(idx);
+        let mut args = Args::new(args);
+        let syscall = match command {
+            "D" => Syscall::Date(Syscall::date_from_str(args.required_str()?)?),
+            "T" => {
+                let arg = args.required_str()?;
+                let (time, offset) = match arg.find(|c| c == '+' || c == '-') {
+                    Some(idx) => (&arg[..idx], Some(Syscall::offset_from_str(&arg[idx..])?)),
+                    None => (arg, None),
+                };
+                Syscall::Time(Syscall::time_from_str(time)?, offset)
+            },
+            "t" => Syscall::GetTime,
+            "C" => Syscall::ClearNotifications,
+            "DN" => Syscall::DismissNotification(args.required()?),
+            "B" => {
+                let brightness: u8 = args.required()?;
+                if brightness > 100 {
+                    return Err(SyscallError::OutOfRange);
+                }
+                Syscall::Brightness(brightness)
+            },
+            "GB" => Syscall::GetBattery,
+            "V" => Syscall::GetVersion,
+            "LN" => Syscall::ListNotifications,
+            "AO" => Syscall::ApplicationOffset,
+            "RB" => Syscall::Reboot(args.optional()?.unwrap_or(0)),
+            "TH" => {
+                let threshold: u16 = args.required()?;
+                if threshold == 0 || threshold > MAX_THRESHOLD {
+                    return Err(SyscallError::OutOfRange);
+                }
+                Syscall::SetThreshold(threshold)
+            },
+            "TC" => Syscall::Calibrate,
+            "VB" => {
+                let ms = args.required()?;
+                let pattern = args.optional()?.unwrap_or(0);
+                if Pattern::from_u8(pattern).is_none() {
+                    return Err(SyscallError::OutOfRange);
+                }
+                Syscall::Vibrate { ms, pattern }
+            },
+            _ => return Err(SyscallError::UnknownCommand),
+        };
+        args.end()?;
+        Ok(syscall)
+    }
+}
+
+/// The arguments of a syscall
+struct Args<'a> {
+    args: Option<core::str::Split<'a, char>>,
+}
+
+impl<'a> Args<'a> {
+    fn new(args: &'a str) -> Self {
+        Self {
+            args: if args.is_empty() { None } else { Some(args.split(ARG_SEPARATOR)) },
+        }
+    }
+
+    /// The next argument, which must be present
+    fn required_str(&mut self) -> Result<&'a str, SyscallError> {
+        match self.optional_str()? {
+            Some(arg) => Ok(arg),
+            None => Err(SyscallError::MissingArg),
+        }
+    }
+
+    /// The next argument, or `None` if there are no more
+    fn optional_str(&mut self) -> Result<Option<&'a str>, SyscallError> {
+        match self.args.as_mut().and_then(|args| args.next()) {
+            Some("") => Err(SyscallError::MissingArg),
+            arg => Ok(arg),
+        }
+    }
+
+    fn required<T: FromStr>(&mut self) -> Result<T, SyscallError> {
+        self.required_str()?.parse().map_err(|_| SyscallError::BadArg)
+    }
+
+    fn optional<T: FromStr>(&mut self) -> Result<Option<T>, SyscallError> {
+        match self.optional_str()? {
+            Some(arg) => arg.parse().map(Some).map_err(|_| SyscallError::BadArg),
+            None => Ok(None),
+        }
+    }
+
+    /// Check every argument has been used
+    fn end(mut self) -> Result<(), SyscallError> {
+        match self.args.as_mut().and_then(|args| args.next()) {
+            Some(_) => Err(SyscallError::BadArg),
+            None => Ok(()),
         }
     }
 }
 
 impl Syscall {
 
-    pub fn execute(self, system: &mut System) {
+    /// Executes the syscall, returning the response to send back to the sender
+    pub fn execute(self, system: &mut System) -> SyscallResponse<'static> {
         match self {
             Syscall::Date(date) => {
                 info!("Setting the date to {:?}", date);
                 system.rtc().set_date(&date);
+                SyscallResponse::Ack
             },
-            Syscall::Time(time) => {
-                info!("Setting the time to {:?}", time);
+            Syscall::Time(time, offset) => {
+                info!("Setting the time to {:?}, offset {:?}", time, offset);
                 system.rtc().set_time(&time);
+                if let Some(offset) = offset {
+                    system.set_utc_offset(offset);
+                }
+                SyscallResponse::Ack
+            },
+            Syscall::GetTime => SyscallResponse::Time(system.rtc().get_time()),
+            Syscall::ClearNotifications => {
+                info!("Clearing notifications");
+                system.nm().clear_all();
+                SyscallResponse::Ack
+            },
+            Syscall::DismissNotification(id) => match system.nm().remove(id) {
+                Ok(()) => SyscallResponse::Ack,
+                Err(err) => {
+                    warn!("Failed to dismiss notification {}: {:?}", id, err);
+                    SyscallResponse::Error(SyscallError::OutOfRange)
+                }
+            },
+            Syscall::Brightness(brightness) => {
+                info!("Setting the brightness to {}%", brightness);
+                system.set_brightness(brightness);
+                SyscallResponse::Ack
+            },
+            Syscall::GetBattery => SyscallResponse::Battery {
+                percentage: system.bms().percentage(),
+                mv: system.bms().voltage(),
+            },
+            Syscall::GetVersion => SyscallResponse::Version(VERSION),
+            Syscall::Reboot(0) => {
+                system.reboot();
+                SyscallResponse::Ack
+            },
+            Syscall::Reboot(delay) => {
+                info!("Rebooting in {}ms", delay);
+                system.schedule_reboot(u32::from(delay));
+                SyscallResponse::Ack
+            },
+            Syscall::SetThreshold(threshold) => {
+                info!("Setting the touch threshold to {}", threshold);
+                system.set_tsc_threshold(threshold);
+                SyscallResponse::Ack
+            },
+            Syscall::Calibrate => {
+                info!("Recalibrating the touch thresholds");
+                system.calibrate_tsc();
+                SyscallResponse::Ack
+            },
+            Syscall::Vibrate { .. } if !system.haptic().has_motor() => SyscallResponse::Error(SyscallError::Unsupported),
+            Syscall::Vibrate { ms, pattern } => match Pattern::from_u8(pattern) {
+                Some(pattern) => {
+                    system.haptic().vibrate(ms, pattern);
+                    SyscallResponse::Ack
+                },
+                None => SyscallResponse::Error(SyscallError::OutOfRange),
+            },
+            Syscall::ListNotifications => SyscallResponse::Notifications(system.nm().idx()),
+            Syscall::ApplicationOffset => SyscallResponse::ApplicationOffset {
+                offset: system.am().received(),
+                checksum: system.am().staged_checksum(),
             },
         }
     }
 
-    pub fn date_from_str(s: &str) -> Result<Date, Error> {
-        let mut vals = [0u32; 4];
-        for (idx, number) in s.split('/').enumerate() {
-            match number.parse() {
-                Ok(val) => vals[idx] = val,
-                Err(e) => {
-                    error!("Failed to convert {} into a integer due to {:?}", number, e);
-                    return Err(Error::ParseError)
-                }
-            }
+    /// Parses a date of the form `day in week/date/month/year`
+    pub fn date_from_str(s: &str) -> Result<Date, SyscallError> {
+        let [day, date, month, year]: [u32; 4] = Syscall::numbers(s, '/')?;
+        Ok(Date::new(day.day(), date.date(), month.month(), year.year()))
+    }
+
+    /// Parses a time of the form `HH:MM:SS`
+    pub fn time_from_str(s: &str) -> Result<Time, SyscallError> {
+        let [hours, minutes, seconds]: [u32; 3] = Syscall::numbers(s, ':')?;
+        Ok(Time::new(hours.hours(), minutes.minutes(), seconds.seconds(), false))
+    }
+
+    /// Parses exactly `N` numbers separated by `separator`
+    fn numbers<N: Default + AsMut<[u32]>>(s: &str, separator: char) -> Result<N, SyscallError> {
+        let mut vals = N::default();
+        let mut numbers = s.split(separator);
+        for val in vals.as_mut().iter_mut() {
+            let number = numbers.next().ok_or(SyscallError::BadArg)?;
+            *val = number.parse().map_err(|e| {
+                error!("Failed to convert {} into a integer due to {:?}", number, e);
+                SyscallError::BadArg
+            })?;
+        }
+        match numbers.next() {
+            Some(_) => Err(SyscallError::BadArg),
+            None => Ok(vals),
         }
-        Ok(Date::new(vals[0].day(), vals[1].date(), vals[2].month(), vals[3].year()))
     }
 
-    pub fn time_from_str(s: &str) -> Result<Time, Error> {
-        let mut vals = [0u32; 3];
-        for (idx, number) in s.split(':').enumerate() {
-            match number.parse() {
-                Ok(val) => vals[idx] = val,
-                Err(e) => {
-                    error!("Failed to convert {} into a integer due to {:?}", number, e);
-                    return Err(Error::ParseError)
-                }
-            }
+    /// Parses a UTC offset of the form `+HH:MM` or `-HH:MM` into minutes
+    pub fn offset_from_str(s: &str) -> Result<i16, SyscallError> {
+        let sign = match s.as_bytes().first() {
+            Some(b'+') => 1,
+            Some(b'-') => -1,
+            _ => return Err(SyscallError::InvalidOffset),
+        };
+        let mut parts = s[1..].split(':');
+        let (hours, minutes) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(hours), Some(minutes), None) if hours.len() == 2 && minutes.len() == 2 => (hours, minutes),
+            _ => return Err(SyscallError::InvalidOffset),
+        };
+        let hours: i16 = hours.parse().map_err(|_| SyscallError::InvalidOffset)?;
+        let minutes: i16 = minutes.parse().map_err(|_| SyscallError::InvalidOffset)?;
+        let offset = hours * 60 + minutes;
+        if minutes >= 60 || offset > MAX_UTC_OFFSET {
+            return Err(SyscallError::InvalidOffset);
         }
-        Ok(Time::new(vals[0].hours(), vals[1].minutes(), vals[2].seconds(), false))
+        Ok(sign * offset)
     }
 }
 
@@ -118,7 +390,7 @@ mod test {
 
         let working = Syscall::from_str("T00:00:00").unwrap();
         match working {
-            Syscall::Time(t) => {
+            Syscall::Time(t, _) => {
                 assert_eq!(actual, t);
             }
             _ => panic!("wrong syscall type")
@@ -126,10 +398,219 @@ mod test {
 
         let working = Syscall::from_str("T01:00:00").unwrap();
         match working {
-            Syscall::Time(t) => {
+            Syscall::Time(t, _) => {
                 assert_ne!(actual, t);
             }
             _ => panic!("wrong syscall type")
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn syscall_errors() {
+        assert_eq!(Syscall::from_str(""), Err(SyscallError::UnknownCommand));
+        assert_eq!(Syscall::from_str("X"), Err(SyscallError::UnknownCommand));
+        assert_eq!(Syscall::from_str("12:00:00"), Err(SyscallError::UnknownCommand));
+
+        assert_eq!(Syscall::from_str("T"), Err(SyscallError::MissingArg));
+        assert_eq!(Syscall::from_str("D"), Err(SyscallError::MissingArg));
+
+        assert_eq!(Syscall::from_str("T12:00"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("T12:00:00:00"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("T12:aa:00"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("T12:00:00,1"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("D1/01/04"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("t1"), Err(SyscallError::BadArg));
+    }
+
+    #[test]
+    fn syscall_response_format() {
+        use core::fmt::Write;
+        let mut out: heapless::String<heapless::consts::U32> = heapless::String::new();
+        let time = Time::new(9.hours(), 5.minutes(), 30.seconds(), false);
+        write!(out, "{}", SyscallResponse::Time(time)).unwrap();
+        assert_eq!(out.as_str(), "T09:05:30");
+        assert_eq!(Syscall::from_str("t"), Ok(Syscall::GetTime));
+
+        let mut out: heapless::String<heapless::consts::U32> = heapless::String::new();
+        write!(out, "{}", SyscallResponse::ApplicationOffset { offset: 1024, checksum: Some(0xAB) }).unwrap();
+        assert_eq!(out.as_str(), "O1024,000000AB");
+        assert_eq!(Syscall::from_str("AO"), Ok(Syscall::ApplicationOffset));
+        assert_eq!(Syscall::from_str("AO1"), Err(SyscallError::BadArg));
+    }
+
+    #[test]
+    fn syscall_time_offset() {
+        let actual = Time::new(12.hours(), 0.minutes(), 0.seconds(), false);
+        assert_eq!(Syscall::from_str("T12:00:00"), Ok(Syscall::Time(actual, None)));
+        assert_eq!(Syscall::from_str("T12:00:00+00:00"), Ok(Syscall::Time(actual, Some(0))));
+        assert_eq!(Syscall::from_str("T12:00:00+02:00"), Ok(Syscall::Time(actual, Some(120))));
+        assert_eq!(Syscall::from_str("T12:00:00-09:30"), Ok(Syscall::Time(actual, Some(-570))));
+
+        assert_eq!(Syscall::from_str("T12:00:00+2"), Err(SyscallError::InvalidOffset));
+        assert_eq!(Syscall::from_str("T12:00:00+02:60"), Err(SyscallError::InvalidOffset));
+        assert_eq!(Syscall::from_str("T12:00:00+15:00"), Err(SyscallError::InvalidOffset));
+        assert_eq!(Syscall::from_str("T12:00:00+0a:00"), Err(SyscallError::InvalidOffset));
+        assert_eq!(Syscall::from_str("T12:00:00+02:00:00"), Err(SyscallError::InvalidOffset));
+    }
+
+    #[test]
+    fn syscall_clear_notifications() {
+        use crate::ingress::buffer::Buffer;
+        let mut system = System::mock();
+        system.nm().add(&Buffer::default(), &[0], 0, 0).unwrap();
+        assert_eq!(system.nm().idx(), 1);
+
+        let syscall = Syscall::from_str("C").unwrap();
+        assert_eq!(syscall, Syscall::ClearNotifications);
+        assert_eq!(syscall.execute(&mut system), SyscallResponse::Ack);
+        assert_eq!(system.nm().idx(), 0);
+    }
+
+    #[test]
+    fn syscall_dismiss_notification() {
+        use crate::ingress::buffer::Buffer;
+        let mut system = System::mock();
+        let first = system.nm().add(&Buffer::default(), &[0], 0, 0).unwrap().id;
+        let second = system.nm().add(&Buffer::default(), &[0], 0, 0).unwrap().id;
+
+        let syscall = Syscall::from_str(&format!("DN{}", first)).unwrap();
+        assert_eq!(syscall, Syscall::DismissNotification(first));
+        assert_eq!(syscall.execute(&mut system), SyscallResponse::Ack);
+        assert_eq!(system.nm().idx(), 1);
+        system.nm().peek_notification(0, |notification| assert_eq!(notification.id(), second));
+        // already dismissed
+        assert_eq!(syscall.execute(&mut system), SyscallResponse::Error(SyscallError::OutOfRange));
+        assert_eq!(Syscall::from_str("DN"), Err(SyscallError::MissingArg));
+    }
+
+    #[test]
+    fn syscall_brightness() {
+        assert_eq!(Syscall::from_str("B0"), Ok(Syscall::Brightness(0)));
+        assert_eq!(Syscall::from_str("B100"), Ok(Syscall::Brightness(100)));
+        assert_eq!(Syscall::from_str("B200"), Err(SyscallError::OutOfRange));
+        assert_eq!(Syscall::from_str("B300"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("B"), Err(SyscallError::MissingArg));
+
+        use crate::system::system::Backlight;
+
+        struct MockBacklight(Option<u8>);
+
+        impl Backlight for MockBacklight {
+            fn set_brightness(&mut self, percent: u8) {
+                self.0 = Some(percent);
+            }
+        }
+
+        let mut system = System::mock();
+        let mut backlight = MockBacklight(None);
+        system.apply_brightness(&mut backlight);
+        assert_eq!(backlight.0, None);
+
+        assert_eq!(Syscall::Brightness(40).execute(&mut system), SyscallResponse::Ack);
+        assert_eq!(system.brightness(), 40);
+        system.apply_brightness(&mut backlight);
+        assert_eq!(backlight.0, Some(40));
+
+        // only applied once
+        backlight.0 = None;
+        system.apply_brightness(&mut backlight);
+        assert_eq!(backlight.0, None);
+    }
+
+    #[test]
+    fn syscall_reboot() {
+        use crate::system::system::ResetTarget;
+        use core::cell::Cell;
+
+        struct MockReset(&'static Cell<bool>);
+
+        impl ResetTarget for MockReset {
+            fn reset(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        assert_eq!(Syscall::from_str("RB"), Ok(Syscall::Reboot(0)));
+        assert_eq!(Syscall::from_str("RB500"), Ok(Syscall::Reboot(500)));
+        assert_eq!(Syscall::from_str("RB5x"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("RX"), Err(SyscallError::UnknownCommand));
+
+        let requested: &'static Cell<bool> = Box::leak(Box::new(Cell::new(false)));
+        let mut system = System::mock();
+        system.set_reset_target(Box::leak(Box::new(MockReset(requested))));
+        assert_eq!(Syscall::Reboot(0).execute(&mut system), SyscallResponse::Ack);
+        assert!(requested.get());
+
+        // a delayed reboot waits for the delay to elapse
+        requested.set(false);
+        assert_eq!(Syscall::Reboot(500).execute(&mut system), SyscallResponse::Ack);
+        system.poll_reboot(333);
+        assert!(!requested.get());
+        system.poll_reboot(666);
+        assert!(requested.get());
+    }
+
+    #[test]
+    fn syscall_set_threshold() {
+        use crate::system::input::{InputManager, Polarity};
+
+        assert_eq!(Syscall::from_str("TH0"), Err(SyscallError::OutOfRange));
+        assert_eq!(Syscall::from_str("TH20000"), Err(SyscallError::OutOfRange));
+        assert_eq!(Syscall::from_str("TH"), Err(SyscallError::MissingArg));
+
+        let syscall = Syscall::from_str("TH1500").unwrap();
+        assert_eq!(syscall, Syscall::SetThreshold(1500));
+        let mut system = System::mock();
+        assert_eq!(syscall.execute(&mut system), SyscallResponse::Ack);
+
+        let mut im = InputManager::mock(1, Polarity::default());
+        let threshold = system.take_tsc_threshold().unwrap();
+        im.set_threshold(threshold).unwrap();
+        assert_eq!(im.thresholds(), [1500; 3]);
+        assert_eq!(system.take_tsc_threshold(), None);
+    }
+
+    #[test]
+    fn syscall_calibrate() {
+        assert_eq!(Syscall::from_str("TC1"), Err(SyscallError::BadArg));
+
+        let syscall = Syscall::from_str("TC").unwrap();
+        assert_eq!(syscall, Syscall::Calibrate);
+        let mut system = System::mock();
+        assert!(!system.take_tsc_calibration());
+        assert_eq!(syscall.execute(&mut system), SyscallResponse::Ack);
+        assert!(system.take_tsc_calibration());
+        assert!(!system.take_tsc_calibration());
+    }
+
+    #[test]
+    fn syscall_vibrate() {
+        assert_eq!(Syscall::from_str("VB200,1"), Ok(Syscall::Vibrate { ms: 200, pattern: 1 }));
+        assert_eq!(Syscall::from_str("VB500"), Ok(Syscall::Vibrate { ms: 500, pattern: 0 }));
+        assert_eq!(Syscall::from_str("V"), Ok(Syscall::GetVersion));
+
+        assert_eq!(Syscall::from_str("VB"), Err(SyscallError::MissingArg));
+        assert_eq!(Syscall::from_str("VB,1"), Err(SyscallError::MissingArg));
+        assert_eq!(Syscall::from_str("VB200,"), Err(SyscallError::MissingArg));
+        assert_eq!(Syscall::from_str("VB200,x"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("VB200,1,1"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("VB70000,1"), Err(SyscallError::BadArg));
+        assert_eq!(Syscall::from_str("VB200,3"), Err(SyscallError::OutOfRange));
+        assert_eq!(Syscall::from_str("VX"), Err(SyscallError::UnknownCommand));
+
+        use crate::system::haptic::Motor;
+
+        struct MockMotor;
+
+        impl Motor for MockMotor {
+            fn set(&mut self, _on: bool) {}
+        }
+
+        let mut system = System::mock();
+        assert_eq!(Syscall::Vibrate { ms: 200, pattern: 2 }.execute(&mut system), SyscallResponse::Error(SyscallError::Unsupported));
+        assert!(!system.haptic().is_vibrating());
+        system.haptic().set_motor(Box::leak(Box::new(MockMotor)));
+        assert_eq!(Syscall::Vibrate { ms: 200, pattern: 2 }.execute(&mut system), SyscallResponse::Ack);
+        assert!(system.haptic().is_vibrating());
+    }
+}
@@ -2,47 +2,168 @@
 //! 
 //! Performs housekeeping of system hardware and provides a nice sofware abstraction to read / manipulate it
 
+use core::fmt::Write;
 use stm32l4xx_hal::rtc::Rtc;
+use stm32l4xx_hal::datetime::{Date, Time};
 use crate::system::bms::BatteryManagement;
-use crate::system::notification::NotificationManager;
+use crate::system::notification::{NotificationManager, NotificationError, MAX_PRIORITY};
+use crate::ingress::buffer::{Buffer, Type};
+use crate::ingress::ingress_manager::IngressManager;
+use crate::system::input::{self, InputManager, TouchSense};
+use crate::system::alarm::Alarm;
+use crate::system::haptic::Haptic;
 use crate::application::application_manager::ApplicationManager;
+use heapless::ArrayLength;
 
 
+/// The kernel version, reported by the version syscall
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub const DMA_HALF_BYTES: usize = 64;
 
 pub const CPU_USAGE_POLL_HZ: u32 = 1; // hz
 pub const SYSTICK_HZ: u32 = 3; // hz
 pub const TSC_HZ: u32 = (8 * 3); // 8 polls per second (for 3 inputs)
+pub const INPUT_DEBOUNCE_SAMPLES: u8 = 1; // consecutive agreeing samples before a pin changes state, 1 is instantaneous
 
 pub const SYS_CLK_HZ: u32 = 16_000_000;
 pub const SPI_MHZ: u32 = SYS_CLK_HZ / 2_000_000; // spi is always half of sysclock
 pub const I2C_KHZ: u32 = 100;
 
 pub const IDLE_TIMEOUT_SECONDS: u32 = 15;
+/// How often `System::tick` reads the battery management
+pub const BATTERY_POLL_MS: u32 = 1000;
+
+/// Hardware that can reset the system
+pub trait ResetTarget {
+    /// Reset the system, on hardware this does not return
+    fn reset(&mut self);
+}
+
+/// A display whose brightness can be set, i.e through the contrast of an OLED
+pub trait Backlight {
+    /// Set the brightness, 0 to 100%
+    fn set_brightness(&mut self, percent: u8);
+}
+
+/// A real time clock
+pub trait Clock {
+    fn get_time(&self) -> Time;
+    fn set_time(&mut self, time: &Time);
+    fn get_date(&self) -> Date;
+    fn set_date(&mut self, date: &Date);
+}
+
+impl Clock for Rtc {
+    fn get_time(&self) -> Time {
+        Rtc::get_time(self)
+    }
+
+    fn set_time(&mut self, time: &Time) {
+        Rtc::set_time(self, time)
+    }
+
+    fn get_date(&self) -> Date {
+        Rtc::get_date(self)
+    }
+
+    fn set_date(&mut self, date: &Date) {
+        Rtc::set_date(self, date)
+    }
+}
 
 /// A grouping of core sysem peripherals
 pub struct System {
-    rtc: Rtc,
+    rtc: &'static mut dyn Clock,
     bms: BatteryManagement,
     nm: NotificationManager,
     am: ApplicationManager,
     stats: Stats,
+    alarm: Alarm,
+    utc_offset: i16,
+    brightness: u8,
+    brightness_changed: bool,
+    reset: Option<&'static mut dyn ResetTarget>,
+    /// the time a reboot was scheduled and its delay
+    reboot: Option<(u32, u32)>,
+    now_ms: u32,
+    tsc_threshold: Option<u16>,
+    tsc_calibrate: bool,
+    haptic: Haptic,
+    battery_poll_ms: Option<u32>,
+    last_battery_ms: Option<u32>,
+}
+
+/// What happened during a `System::tick`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TickEvents {
+    /// A partial ingress packet timed out and was dropped
+    pub partial_dropped: bool,
+    /// The number of notifications removed by the ttl sweep
+    pub notifications_expired: usize,
+    /// The battery became low
+    pub battery_low: bool,
+    /// The error the input manager recovered from, i.e an acquisition timeout
+    pub input: Option<input::Error>,
+    /// Failure to add a system notification
+    pub notify: Option<NotificationError>,
 }
 
 impl System {
-    pub fn new(rtc: Rtc, bms: BatteryManagement, nm: NotificationManager, am: ApplicationManager) -> Self {
+    pub fn new(rtc: &'static mut dyn Clock, bms: BatteryManagement, nm: NotificationManager, am: ApplicationManager) -> Self {
         Self {
             rtc,
             bms,
             nm,
             am,
             stats: Stats::default(),
+            alarm: Alarm::default(),
+            utc_offset: 0,
+            brightness: 100,
+            brightness_changed: false,
+            reset: None,
+            reboot: None,
+            now_ms: 0,
+            tsc_threshold: None,
+            tsc_calibrate: false,
+            haptic: Haptic::default(),
+            battery_poll_ms: Some(BATTERY_POLL_MS),
+            last_battery_ms: None,
+        }
+    }
+
+    /// Sets the hardware used to reboot the system
+    pub fn set_reset_target(&mut self, target: &'static mut dyn ResetTarget) {
+        self.reset = Some(target);
+    }
+
+    /// Reboot the system immediately
+    pub fn reboot(&mut self) {
+        info!("Rebooting");
+        match self.reset.as_mut() {
+            Some(target) => target.reset(),
+            None => error!("No reset target, cannot reboot"),
+        }
+    }
+
+    /// Reboot the system once `delay_ms` has elapsed since the last `tick`, see `poll_reboot`
+    pub fn schedule_reboot(&mut self, delay_ms: u32) {
+        self.reboot = Some((self.now_ms, delay_ms));
+    }
+
+    /// Reboot if a scheduled reboot is due at `now_ms`, called by `tick`
+    pub fn poll_reboot(&mut self, now_ms: u32) {
+        if let Some((scheduled_ms, delay_ms)) = self.reboot {
+            if now_ms.wrapping_sub(scheduled_ms) >= delay_ms {
+                self.reboot = None;
+                self.reboot();
+            }
         }
     }
 
     /// Real time clock
-    pub fn rtc(&mut self) -> &mut Rtc {
-        &mut self.rtc
+    pub fn rtc(&mut self) -> &mut dyn Clock {
+        self.rtc
     }
 
     /// Battery management
@@ -60,6 +181,180 @@ impl System {
         &mut self.nm
     }
 
+    /// Alarm, see `poll_alarm`
+    pub fn alarm(&mut self) -> &mut Alarm {
+        &mut self.alarm
+    }
+
+    /// Vibration motor
+    pub fn haptic(&mut self) -> &mut Haptic {
+        &mut self.haptic
+    }
+
+    /// Check the alarm against the real time clock, returns true if it fired
+    pub fn poll_alarm(&mut self) -> bool {
+        let now = self.rtc.get_time();
+        self.alarm.poll(&now)
+    }
+
+    /// The UTC offset of the real time clock in minutes, as set by the time syscall
+    pub fn utc_offset(&self) -> i16 {
+        self.utc_offset
+    }
+
+    pub fn set_utc_offset(&mut self, minutes: i16) {
+        self.utc_offset = minutes;
+    }
+
+    /// The unix time of the real time clock in seconds, corrected by the UTC offset
+    pub fn timestamp(&mut self) -> u32 {
+        let date = self.rtc.get_date();
+        let time = self.rtc.get_time();
+        let seconds = days_since_epoch(date.year, date.month, date.date) * 86_400
+            + i64::from(time.hours * 3600 + time.minutes * 60 + time.seconds)
+            - i64::from(self.utc_offset) * 60;
+        seconds.max(0) as u32
+    }
+
+    /// The display brightness, 0 to 100%
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Sets the display brightness, clamped to 100%. The display is not part of the system, so the
+    /// brightness is applied by the display task, see `apply_brightness`
+    pub fn set_brightness(&mut self, percent: u8) {
+        self.brightness = percent.min(100);
+        self.brightness_changed = true;
+    }
+
+    /// Apply the brightness to `backlight` if it has changed since it was last applied
+    pub fn apply_brightness(&mut self, backlight: &mut dyn Backlight) {
+        if core::mem::replace(&mut self.brightness_changed, false) {
+            backlight.set_brightness(self.brightness);
+        }
+    }
+
+    /// Request a new touch threshold for all inputs, the input manager is not part of the system
+    /// so the request is applied by the system tick, see `take_tsc_threshold`
+    pub fn set_tsc_threshold(&mut self, threshold: u16) {
+        self.tsc_threshold = Some(threshold);
+    }
+
+    /// Take the pending touch threshold request, if there is one
+    pub fn take_tsc_threshold(&mut self) -> Option<u16> {
+        self.tsc_threshold.take()
+    }
+
+    /// Request the touch thresholds are recalibrated, applied by the system tick like `set_tsc_threshold`
+    pub fn calibrate_tsc(&mut self) {
+        self.tsc_calibrate = true;
+    }
+
+    /// Take the pending calibration request, returns true if there was one
+    pub fn take_tsc_calibration(&mut self) -> bool {
+        core::mem::replace(&mut self.tsc_calibrate, false)
+    }
+
+    /// Process the battery management, notifying when the battery becomes low
+    pub fn poll_battery(&mut self) -> Result<(), NotificationError> {
+        self.bms.process();
+        let percentage = self.bms.percentage();
+        self.check_battery(percentage)
+    }
+
+    /// Sets how often `tick` reads the battery management, `None` disables reading it
+    pub fn set_battery_poll(&mut self, interval_ms: Option<u32>) {
+        self.battery_poll_ms = interval_ms;
+    }
+
+    /// Perform the time driven work of the system and the managers it does not own, in order: the ingress
+    /// partial packet timeout, the notification ttl sweep, the battery poll, the input acquisition timeout,
+    /// the haptic pattern and any scheduled reboot. Every manager is given `now_ms` as its time
+    pub fn tick<N, T>(&mut self, now_ms: u32, ingress: &mut IngressManager<N>, input: &mut InputManager<T>) -> TickEvents
+    where
+        N: ArrayLength<u8>,
+        T: TouchSense,
+    {
+        let mut events = TickEvents::default();
+        self.now_ms = now_ms;
+
+        let dropped = ingress.dropped_partial();
+        ingress.tick(now_ms);
+        events.partial_dropped = ingress.dropped_partial() != dropped;
+
+        let stored = self.nm.idx();
+        self.nm.sweep(now_ms);
+        events.notifications_expired = stored - self.nm.idx();
+
+        if self.battery_poll_due(now_ms) {
+            self.bms.process();
+        }
+        // no reading has been taken yet
+        if self.bms.voltage() != 0 {
+            let was_low = self.bms.is_low();
+            let percentage = self.bms.percentage();
+            events.notify = self.check_battery(percentage).err();
+            events.battery_low = !was_low && self.bms.is_low();
+        }
+
+        input.tick(now_ms);
+        events.input = input.poll().err();
+
+        self.haptic.tick(now_ms);
+        self.poll_reboot(now_ms);
+        events
+    }
+
+    fn battery_poll_due(&mut self, now_ms: u32) -> bool {
+        match (self.battery_poll_ms, self.last_battery_ms) {
+            (None, _) => false,
+            (Some(interval), Some(last)) if now_ms.wrapping_sub(last) < interval => false,
+            _ => {
+                self.last_battery_ms = Some(now_ms);
+                true
+            }
+        }
+    }
+
+    /// Add a notification if the battery `percentage` has fallen below the low threshold
+    fn check_battery(&mut self, percentage: u8) -> Result<(), NotificationError> {
+        if !self.bms.update_low(percentage) {
+            return Ok(());
+        }
+        warn!("Battery low, {}%", percentage);
+        let mut body: heapless::String<heapless::consts::U32> = heapless::String::new();
+        let _ = write!(body, "Battery at {}%", percentage);
+        self.notify("Low battery", body.as_str())
+    }
+
+    /// Check the application watchdog, notifying if the running application was killed
+    pub fn poll_watchdog(&mut self, now_ms: u32) -> Result<(), NotificationError> {
+        match self.am.tick(now_ms) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                let mut body: heapless::String<heapless::consts::U32> = heapless::String::new();
+                let _ = write!(body, "{:?}", err);
+                self.notify("Application killed", body.as_str())
+            }
+        }
+    }
+
+    /// Add a notification from the system
+    fn notify(&mut self, title: &str, body: &str) -> Result<(), NotificationError> {
+        let mut buffer = Buffer::default();
+        buffer.btype = Type::Notification;
+        let mut idxs = [0usize; 3];
+        for (idx, section) in ["System", title, body].iter().enumerate() {
+            for byte in section.bytes() {
+                buffer.write(byte).map_err(|_| NotificationError::Parsing)?;
+            }
+            idxs[idx] = buffer.len();
+        }
+        let timestamp = self.timestamp();
+        self.nm.add(&buffer, &idxs, MAX_PRIORITY, timestamp).map(|_| ())
+    }
+
     /// System stats
     pub fn ss(&mut self) -> &mut Stats {
         &mut self.stats
@@ -83,12 +378,75 @@ impl System {
     }
 }
 
+/// The number of days from 1/1/1970 to the date, in the proleptic Gregorian calendar
+fn days_since_epoch(year: u32, month: u32, day: u32) -> i64 {
+    let year = i64::from(year) - if month <= 2 { 1 } else { 0 };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// A clock for testing, which keeps the time and date it was last set to
+#[cfg(test)]
+pub(crate) struct MockClock {
+    time: Time,
+    date: Date,
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn get_time(&self) -> Time {
+        self.time
+    }
+
+    fn set_time(&mut self, time: &Time) {
+        self.time = *time;
+    }
+
+    fn get_date(&self) -> Date {
+        self.date
+    }
+
+    fn set_date(&mut self, date: &Date) {
+        self.date = *date;
+    }
+}
+
+#[cfg(test)]
+impl System {
+    /// Creates a system for testing, with a `MockClock` starting at midnight on 1/1/2019.
+    /// The other hardware peripherals must not be used, so `tick` does not read the battery
+    pub(crate) fn mock() -> Self {
+        use crate::application::application_manager::Ram;
+        use crate::types::hal::prelude::*;
+        let ram: &'static mut [u8] = Box::leak(vec![0u8; 1024].into_boxed_slice());
+        let clock = MockClock {
+            time: Time::new(0.hours(), 0.minutes(), 0.seconds(), false),
+            date: Date::new(1.day(), 1.date(), 1.month(), 2019.year()),
+        };
+        let mut system = unsafe {
+            // safe because we dont use the hw in tests
+            System::new(
+                Box::leak(Box::new(clock)),
+                BatteryManagement::new(core::mem::uninitialized(), core::mem::uninitialized(), core::mem::uninitialized()),
+                NotificationManager::new(),
+                ApplicationManager::new(Ram::new(ram)),
+            )
+        };
+        system.set_battery_poll(None);
+        system
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Stats {
     pub cpu_usage: f32,
     pub tsc_events: u32,
     pub idle_count: u32,
-    pub tsc_threshold: u16,
+    pub tsc_threshold: [u16; 3],
 }
     
 
@@ -98,8 +456,98 @@ impl Default for Stats {
             cpu_usage: 0.0,
             tsc_events: 0,
             idle_count: 0,
-            tsc_threshold: 0,
+            tsc_threshold: [0; 3],
         }
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn low_battery_notification() {
+        let mut system = System::mock();
+        for percentage in (0..=30).rev() {
+            system.check_battery(percentage).unwrap();
+        }
+        assert_eq!(system.nm().idx(), 1);
+        system.nm().peek_notification(0, |n| {
+            assert_eq!(n.source(), "System");
+            assert_eq!(n.title(), "Low battery");
+            assert_eq!(n.body(), "Battery at 14%");
+        });
+
+        // flapping at the boundary does not notify again
+        for &percentage in [15, 14, 16, 13, 19, 14].iter() {
+            system.check_battery(percentage).unwrap();
+        }
+        assert_eq!(system.nm().idx(), 1);
+
+        // recharged past the hysteresis, then drained again
+        system.check_battery(20).unwrap();
+        system.check_battery(14).unwrap();
+        assert_eq!(system.nm().idx(), 2);
+    }
+
+    #[test]
+    fn timestamp() {
+        use crate::types::hal::prelude::*;
+        let mut system = System::mock();
+        assert_eq!(system.timestamp(), 1_546_300_800);
+        system.rtc().set_date(&Date::new(4.day(), 29.date(), 2.month(), 2024.year()));
+        system.rtc().set_time(&Time::new(13.hours(), 45.minutes(), 10.seconds(), false));
+        assert_eq!(system.timestamp(), 1_709_214_310);
+        // the clock is an hour ahead of UTC
+        system.set_utc_offset(60);
+        assert_eq!(system.timestamp(), 1_709_210_710);
+    }
+
+    #[test]
+    fn tick() {
+        use crate::ingress::ingress_manager::PARTIAL_TIMEOUT_MS;
+        use crate::system::input::Polarity;
+        use crate::system::haptic::Pattern;
+
+        let mut system = System::mock();
+        let mut imgr: IngressManager = IngressManager::new();
+        let mut im = InputManager::mock(1, Polarity::default());
+        assert_eq!(system.tick(0, &mut imgr, &mut im), TickEvents::default());
+
+        // a partial packet, a notification and a stalled acquisition
+        imgr.write(b"\x02N\x1Fsrc").unwrap();
+        imgr.process(&mut system).unwrap();
+        system.nm().set_ttl(Some(5000));
+        system.notify("title", "body").unwrap();
+        im.set_acquisition_timeout(100);
+        im.start_new().unwrap();
+        system.bms().set_voltage(4000);
+
+        let events = system.tick(PARTIAL_TIMEOUT_MS + 1, &mut imgr, &mut im);
+        assert!(events.partial_dropped);
+        assert_eq!(events.input, Some(input::Error::AcquisitionTimeout));
+        assert_eq!(events.notifications_expired, 0);
+        assert!(!events.battery_low);
+
+        system.bms().set_voltage(3400);
+        let events = system.tick(5000, &mut imgr, &mut im);
+        assert_eq!(events, TickEvents {
+            notifications_expired: 1,
+            battery_low: true,
+            ..TickEvents::default()
+        });
+        // the low battery notification
+        assert_eq!(system.nm().idx(), 1);
+        assert_eq!(system.tick(5001, &mut imgr, &mut im), TickEvents::default());
+
+        // the haptic pattern and a scheduled reboot are timed from the last tick
+        system.haptic().vibrate(100, Pattern::Single);
+        system.schedule_reboot(100);
+        system.tick(5100, &mut imgr, &mut im);
+        assert!(system.haptic().is_vibrating());
+        assert!(system.reboot.is_some());
+        system.tick(5101, &mut imgr, &mut im);
+        assert!(!system.haptic().is_vibrating());
+        assert!(system.reboot.is_none());
+    }
+}
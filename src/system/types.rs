@@ -60,6 +60,7 @@ pub type InputFn = extern "C" fn(*mut Context, InputEvent) -> i32;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum InputEvent {
     Left,
     Middle,
@@ -68,6 +69,46 @@ pub enum InputEvent {
     Multi,
     LeftMiddle,
     RightMiddle,
+    /// The left input has been held for the long press threshold
+    LongPressLeft,
+    /// The middle input has been held for the long press threshold
+    LongPressMiddle,
+    /// The right input has been held for the long press threshold
+    LongPressRight,
+    /// The left and right inputs have been held for the long press threshold
+    LongPressDual,
+    /// All inputs have been held for the long press threshold
+    LongPressMulti,
+    /// The left and middle inputs have been held for the long press threshold
+    LongPressLeftMiddle,
+    /// The right and middle inputs have been held for the long press threshold
+    LongPressRightMiddle,
+    /// All inputs were held for the reset combo threshold, a hidden gesture for e.g. a factory reset
+    ResetCombo,
+    /// The middle input was tapped twice in quick succession
+    DoubleMiddle,
+    /// A finger was dragged from the left input to the right input
+    SwipeRight,
+    /// A finger was dragged from the right input to the left input
+    SwipeLeft,
+    /// All inputs were released after only the left input was touched
+    ReleasedLeft,
+    /// All inputs were released after only the middle input was touched
+    ReleasedMiddle,
+    /// All inputs were released after only the right input was touched
+    ReleasedRight,
+    /// All inputs were released after the left and right inputs were touched
+    ReleasedDual,
+    /// All inputs were released after all of them were touched
+    ReleasedMulti,
+    /// All inputs were released after the left and middle inputs were touched
+    ReleasedLeftMiddle,
+    /// All inputs were released after the right and middle inputs were touched
+    ReleasedRightMiddle,
+    /// The watch was placed on the charger
+    ChargingStarted,
+    /// The watch was removed from the charger, or finished charging
+    ChargingStopped,
 }
 
 pub static mut CONTEXT_POINTER: Option<&'static mut Context> = None;
@@ -75,6 +116,8 @@ pub static mut CONTEXT_POINTER: Option<&'static mut Context> = None;
 pub struct Context<'a> {
     pub display: Option<&'a mut Ssd1351>,
     pub log: extern "C" fn(&str) -> i32,
+    /// Set when the application pets the watchdog
+    pub watchdog: Option<&'a mut bool>,
 }
 
 /// WARNING only safe if we guarentee the safety ourselves, i.e context doesn't live longer than the &mut references that it contains
@@ -87,11 +130,14 @@ pub struct Table {
     pub draw_pixel: unsafe extern "C" fn(*mut Context, u8, u8, u16) -> i32,
     /// Print a string using th info! macro
     pub print: unsafe extern "C" fn(*mut Context, &str) -> i32,
+    /// Tell the kernel the application is still making progress, see `ApplicationManager::set_exec_timeout`
+    pub pet_watchdog: unsafe extern "C" fn(*mut Context) -> i32,
 }
 
 pub static CALLBACK_TABLE: Table = Table {
     draw_pixel,
-    print
+    print,
+    pet_watchdog,
 };
 
 impl<'a> Context<'a> {
@@ -124,3 +170,11 @@ pub unsafe extern "C" fn print(context: *mut Context, string: &str) -> i32 {
     (ctx.log)(string);
     0
 }
+
+pub unsafe extern "C" fn pet_watchdog(context: *mut Context) -> i32 {
+    let ctx = &mut *context;
+    if let Some(watchdog) = &mut ctx.watchdog {
+        **watchdog = true;
+    }
+    0
+}